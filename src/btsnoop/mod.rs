@@ -0,0 +1,257 @@
+//! Pad traffic out of btsnoop captures, as written by `btmon -w` or found in
+//! Android bug reports.
+//!
+//! The HCI ACL packets are reassembled into L2CAP frames and the ATT PDUs on
+//! them picked: write commands and requests going to fe02 and notifications
+//! coming from fe01. The handles of fe01/fe02 are learnt from the
+//! characteristic discovery when the capture has it. Otherwise every write
+//! starting with the command header and every notification starting with the
+//! response header is taken. The result is the same `Event`s a `Recorder`
+//! writes, so a capture can be annotated or replayed just like a recording.
+
+use crate::controller::protocol::{CMD_HEADER, RESP_HEADER};
+use crate::recording::{Direction, Event};
+
+use derive_more::{Display, Error as DError};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::Path;
+
+const MAGIC: &[u8] = b"btsnoop\0";
+const HEADER_LEN: usize = 16;
+const RECORD_HEADER_LEN: usize = 24;
+
+const DATALINK_HCI: u32 = 1001;
+const DATALINK_H4: u32 = 1002;
+const DATALINK_MONITOR: u32 = 2001;
+
+const H4_ACL: u8 = 0x02;
+const FLAG_RECEIVED: u32 = 0x01;
+const FLAG_COMMAND_EVENT: u32 = 0x02;
+const MONITOR_ACL_TX: u32 = 4;
+const MONITOR_ACL_RX: u32 = 5;
+
+const PB_CONTINUATION: u16 = 0b01;
+const CID_ATT: u16 = 0x0004;
+
+const ATT_READ_BY_TYPE_RESP: u8 = 0x09;
+const ATT_WRITE_REQ: u8 = 0x12;
+const ATT_NOTIFY: u8 = 0x1b;
+const ATT_INDICATE: u8 = 0x1d;
+const ATT_WRITE_CMD: u8 = 0x52;
+
+const FE01: u16 = 0xfe01;
+const FE02: u16 = 0xfe02;
+
+#[derive(Display, Debug, DError)]
+pub struct BtsnoopError {
+    pub details: String,
+}
+
+impl From<std::io::Error> for BtsnoopError {
+    fn from(e: std::io::Error) -> Self {
+        BtsnoopError {
+            details: e.to_string(),
+        }
+    }
+}
+
+/// Whether `data` starts like a btsnoop capture.
+pub fn is_btsnoop(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn read(path: &Path) -> Result<Vec<Event>, BtsnoopError> {
+    parse(&std::fs::read(path)?)
+}
+
+/// Pad traffic in a whole btsnoop capture, timed from its first record.
+pub fn parse(data: &[u8]) -> Result<Vec<Event>, BtsnoopError> {
+    if data.len() < HEADER_LEN || !is_btsnoop(data) {
+        return Err(BtsnoopError {
+            details: "Not a btsnoop capture".to_string(),
+        });
+    }
+
+    let datalink = u32_be(&data[12..16]);
+    if ![DATALINK_HCI, DATALINK_H4, DATALINK_MONITOR].contains(&datalink) {
+        return Err(BtsnoopError {
+            details: format!("Unsupported datalink type {}", datalink),
+        });
+    }
+
+    let mut decoder = Decoder::default();
+    let mut first = None;
+    let mut rest = &data[HEADER_LEN..];
+
+    while rest.len() >= RECORD_HEADER_LEN {
+        let included = u32_be(&rest[4..8]) as usize;
+        let flags = u32_be(&rest[8..12]);
+        let timestamp = u64_be(&rest[16..24]);
+        if rest.len() < RECORD_HEADER_LEN + included {
+            warn!("Capture ends in the middle of a record");
+            break;
+        }
+
+        let packet = &rest[RECORD_HEADER_LEN..RECORD_HEADER_LEN + included];
+        rest = &rest[RECORD_HEADER_LEN + included..];
+
+        let at_ms = timestamp.saturating_sub(*first.get_or_insert(timestamp)) / 1000;
+        match acl(datalink, flags, packet) {
+            Some((sent, acl)) => decoder.acl(at_ms, sent, acl),
+            None => continue,
+        }
+    }
+
+    info!(
+        "Found {} pad packets, fe01 handle {:?}, fe02 handle {:?}",
+        decoder.events.len(),
+        decoder.fe01,
+        decoder.fe02
+    );
+    Ok(decoder.events)
+}
+
+/// The ACL packet in a record and whether it was sent by the host.
+fn acl(datalink: u32, flags: u32, packet: &[u8]) -> Option<(bool, &[u8])> {
+    match datalink {
+        DATALINK_H4 => match packet.split_first() {
+            Some((&H4_ACL, acl)) => Some((flags & FLAG_RECEIVED == 0, acl)),
+            _ => None,
+        },
+        DATALINK_HCI if flags & FLAG_COMMAND_EVENT == 0 => {
+            Some((flags & FLAG_RECEIVED == 0, packet))
+        }
+        // The monitor keeps the opcode in the low half and the adapter index
+        // in the high half of the flags.
+        DATALINK_MONITOR => match flags & 0xffff {
+            MONITOR_ACL_TX => Some((true, packet)),
+            MONITOR_ACL_RX => Some((false, packet)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Decoder {
+    fe01: Option<u16>,
+    fe02: Option<u16>,
+    /// L2CAP frames still being reassembled, by connection handle.
+    pending: HashMap<u16, Vec<u8>>,
+    events: Vec<Event>,
+}
+
+impl Decoder {
+    fn acl(&mut self, at_ms: u64, sent: bool, packet: &[u8]) {
+        if packet.len() < 4 {
+            return;
+        }
+        let header = u16_le(&packet[0..2]);
+        let connection = header & 0x0fff;
+        let boundary = (header >> 12) & 0b11;
+        let len = (u16_le(&packet[2..4]) as usize).min(packet.len() - 4);
+        let payload = &packet[4..4 + len];
+
+        let frame = self.pending.entry(connection).or_default();
+        if boundary != PB_CONTINUATION {
+            frame.clear();
+        }
+        frame.extend_from_slice(payload);
+
+        if frame.len() < 4 {
+            return;
+        }
+        let l2cap_len = u16_le(&frame[0..2]) as usize;
+        if frame.len() < 4 + l2cap_len {
+            return;
+        }
+
+        let frame = self.pending.remove(&connection).unwrap_or_default();
+        if u16_le(&frame[2..4]) == CID_ATT {
+            self.att(at_ms, sent, &frame[4..4 + l2cap_len]);
+        }
+    }
+
+    fn att(&mut self, at_ms: u64, sent: bool, pdu: &[u8]) {
+        let Some((&opcode, params)) = pdu.split_first() else {
+            return;
+        };
+
+        match opcode {
+            ATT_READ_BY_TYPE_RESP if !sent => self.learn(params),
+            ATT_WRITE_CMD | ATT_WRITE_REQ if sent && params.len() >= 2 => {
+                let handle = u16_le(&params[0..2]);
+                let value = &params[2..];
+                if matches(self.fe02, handle, value, CMD_HEADER) {
+                    self.push(at_ms, Direction::Write, value);
+                }
+            }
+            ATT_NOTIFY | ATT_INDICATE if !sent && params.len() >= 2 => {
+                let handle = u16_le(&params[0..2]);
+                let value = &params[2..];
+                if matches(self.fe01, handle, value, RESP_HEADER) {
+                    self.push(at_ms, Direction::Notify, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Picks the fe01/fe02 value handles out of characteristic declarations.
+    fn learn(&mut self, params: &[u8]) {
+        let Some((&len, entries)) = params.split_first() else {
+            return;
+        };
+        // Handle, properties, value handle and a 16 or 128-bit UUID.
+        if len != 7 && len != 21 {
+            return;
+        }
+
+        for entry in entries.chunks_exact(len as usize) {
+            let value_handle = u16_le(&entry[3..5]);
+            let uuid = &entry[5..];
+            // A 128-bit UUID on the Bluetooth base UUID has the 16-bit one in
+            // its bytes 12 and 13, little-endian like everything in ATT.
+            let uuid = match uuid.len() {
+                2 => u16_le(uuid),
+                _ => u16_le(&uuid[12..14]),
+            };
+            match uuid {
+                FE01 => self.fe01 = Some(value_handle),
+                FE02 => self.fe02 = Some(value_handle),
+                _ => {}
+            }
+        }
+    }
+
+    fn push(&mut self, at_ms: u64, direction: Direction, data: &[u8]) {
+        self.events.push(Event {
+            at_ms,
+            direction,
+            data: data.to_vec(),
+        });
+    }
+}
+
+/// The known handle, or anything that starts with `header` when it is unknown.
+fn matches(known: Option<u16>, handle: u16, value: &[u8], header: u8) -> bool {
+    match known {
+        Some(known) => known == handle,
+        None => value.first() == Some(&header),
+    }
+}
+
+fn u16_le(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+fn u32_be(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn u64_be(data: &[u8]) -> u64 {
+    data[..8]
+        .iter()
+        .fold(0, |acc, byte| (acc << 8) | *byte as u64)
+}
@@ -0,0 +1,68 @@
+//! Settings of the daemon read from a TOML file.
+//!
+//! ```toml
+//! [profile]
+//! weight_kg = 70
+//! height_cm = 175
+//! age = 30
+//! sex = "female"
+//!
+//! [ramps]
+//! warm_up_to = 4.0
+//! warm_up_secs = 30
+//! cool_down_secs = 20
+//!
+//! [safety]
+//! lease_secs = 15
+//!
+//! [[safety.walk_off]]
+//! up_to = 3.0
+//! secs = 8
+//! ```
+
+use crate::controller::ramp::Ramps;
+use crate::profile::Profile;
+use crate::safety::Safety;
+
+use derive_more::{Display, Error as DError};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Display, Debug, DError)]
+pub struct ConfigError {
+    pub details: String,
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError {
+            details: e.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Who walks, calories are only estimated with it.
+    pub profile: Option<Profile>,
+    /// Warm-up and cool-down of every pad.
+    #[serde(default)]
+    pub ramps: Ramps,
+    #[serde(default)]
+    pub safety: Safety,
+}
+
+impl Config {
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        Config::parse(&std::fs::read_to_string(path)?).map_err(|e| ConfigError {
+            details: format!("{}: {}", path.display(), e.details),
+        })
+    }
+
+    pub fn parse(toml: &str) -> Result<Self, ConfigError> {
+        toml::from_str(toml).map_err(|e| ConfigError {
+            details: e.to_string(),
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! `Transport` over a btleplug peripheral.
+//!
+//! Commands are written to fe02 without response and the pad notifies on fe01.
+//! On a reconnect the peripheral is optionally looked up again with `Rescan`,
+//! and the characteristics are resolved anew.
+
+use super::error::TransportError;
+use super::transport::{Notifications, Transport};
+
+use async_trait::async_trait;
+use btleplug::api::{Characteristic, Peripheral, WriteType};
+use futures::future::BoxFuture;
+use futures::stream::StreamExt;
+use log::info;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+const FE01: &str = "0000fe01";
+const FEO2: &str = "0000fe02";
+
+/// Finds the pad again after it went away, e.g. by scanning the adapter.
+pub type Rescan<T> = Arc<dyn Fn() -> BoxFuture<'static, Result<T, btleplug::Error>> + Send + Sync>;
+
+pub struct BtleTransport<T: Peripheral + 'static> {
+    peripheral: T,
+    rescan: Option<Rescan<T>>,
+    /// fe01 and fe02, once connected.
+    chars: Option<(Characteristic, Characteristic)>,
+}
+
+impl<T: Peripheral + 'static> BtleTransport<T> {
+    pub fn new(peripheral: T) -> Self {
+        Self {
+            peripheral,
+            rescan: None,
+            chars: None,
+        }
+    }
+
+    /// Like `new`, but the pad is looked up again with `rescan` on every
+    /// reconnect instead of reusing the same peripheral.
+    pub fn with_rescan(peripheral: T, rescan: Rescan<T>) -> Self {
+        Self {
+            peripheral,
+            rescan: Some(rescan),
+            chars: None,
+        }
+    }
+
+    fn log_services(&self) {
+        info!("Discover peripheral services...");
+        for service in self.peripheral.services() {
+            info!(
+                "Service UUID {}, primary: {}",
+                service.uuid, service.primary
+            );
+            for characteristic in service.characteristics {
+                info!("  {:?}", characteristic);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Peripheral + 'static> Transport for BtleTransport<T> {
+    async fn connect(&mut self) -> Result<(), TransportError> {
+        let reconnecting = self.chars.take().is_some();
+
+        if reconnecting {
+            if let Some(rescan) = &self.rescan {
+                info!("Rescanning");
+                self.peripheral = rescan().await?;
+            }
+            if self.peripheral.is_connected().await? {
+                self.peripheral.disconnect().await?;
+            }
+        }
+
+        if !self.peripheral.is_connected().await? {
+            info!("Connecting");
+            self.peripheral.connect().await?;
+            info!("Connected!");
+        }
+
+        if reconnecting || self.peripheral.characteristics().is_empty() {
+            self.peripheral.discover_services().await?;
+        }
+        self.log_services();
+
+        let characteristics = self.peripheral.characteristics();
+        let fe01 = find_char(FE01, &characteristics)?;
+        let fe02 = find_char(FEO2, &characteristics)?;
+
+        info!("Subscribing");
+        self.peripheral.subscribe(&fe01).await?;
+        self.chars = Some((fe01, fe02));
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        self.chars = None;
+        Ok(self.peripheral.disconnect().await?)
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let (_, fe02) = self.chars.as_ref().ok_or(TransportError::NotConnected)?;
+        Ok(self
+            .peripheral
+            .write(fe02, frame, WriteType::WithoutResponse)
+            .await?)
+    }
+
+    async fn notifications(&mut self) -> Result<Notifications, TransportError> {
+        let (fe01, _) = self.chars.as_ref().ok_or(TransportError::NotConnected)?;
+        let uuid = fe01.uuid;
+        let notifications = self
+            .peripheral
+            .notifications()
+            .await?
+            .filter_map(move |data| {
+                futures::future::ready((data.uuid == uuid).then_some(data.value))
+            });
+
+        Ok(Box::pin(notifications))
+    }
+}
+
+fn find_char(
+    match_str: &str,
+    characteristics: &BTreeSet<Characteristic>,
+) -> Result<Characteristic, btleplug::Error> {
+    characteristics
+        .iter()
+        .find(|char| char.uuid.to_string().contains(match_str))
+        .cloned()
+        .ok_or_else(|| {
+            btleplug::Error::NotSupported(format!("Characteristic {} not found", match_str))
+        })
+}
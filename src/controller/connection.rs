@@ -0,0 +1,111 @@
+//! Task owning the transport to the pad.
+//!
+//! `Pad` is only a handle to this task: every operation goes over a channel with
+//! a oneshot for the reply, so nothing holds a lock while writes are paced.
+//! Control requests (connect, notifications, disconnect) are served ahead of
+//! queued commands and never wait for `MIN_TIME_BETWEEN_CMDS`. Queued commands
+//! are ordered and coalesced by the `Scheduler`.
+
+use super::error::TransportError;
+use super::protocol::Command;
+use super::scheduler::Scheduler;
+use super::transport::{Notifications, Transport};
+
+use log::{info, trace};
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+pub(crate) const MIN_TIME_BETWEEN_CMDS: Duration = Duration::from_millis(690);
+
+pub(crate) type Reply<R> = oneshot::Sender<Result<R, TransportError>>;
+
+pub(crate) enum Control {
+    Connect(Reply<()>),
+    Notifications(Reply<Notifications>),
+    Disconnect(Reply<()>),
+}
+
+pub(crate) struct Write {
+    pub command: Command,
+    pub reply: Reply<()>,
+}
+
+pub(crate) struct Connection<T: Transport> {
+    transport: T,
+    control: mpsc::UnboundedReceiver<Control>,
+    commands: mpsc::UnboundedReceiver<Write>,
+}
+
+impl<T: Transport> Connection<T> {
+    pub fn spawn(transport: T) -> (mpsc::UnboundedSender<Control>, mpsc::UnboundedSender<Write>) {
+        let (control_tx, control) = mpsc::unbounded_channel();
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+
+        let connection = Connection {
+            transport,
+            control,
+            commands,
+        };
+        tokio::spawn(connection.run());
+
+        (control_tx, commands_tx)
+    }
+
+    async fn run(mut self) {
+        let mut next_write = Instant::now();
+        let mut scheduler = Scheduler::default();
+
+        loop {
+            tokio::select! {
+                biased;
+                control = self.control.recv() => match control {
+                    Some(control) => {
+                        if !self.handle(control).await {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                write = self.commands.recv() => match write {
+                    Some(write) => scheduler.push(write),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(next_write), if !scheduler.is_empty() => {
+                    if let Some((command, replies)) = scheduler.pop() {
+                        trace!("Writing {:?}", command);
+                        let res = self.transport.write(&command.encode()).await;
+                        next_write = Instant::now() + MIN_TIME_BETWEEN_CMDS;
+                        Connection::<T>::reply_all(replies, res);
+                    }
+                }
+            }
+        }
+
+        info!("Connection task finished");
+    }
+
+    fn reply_all(replies: Vec<Reply<()>>, res: Result<(), TransportError>) {
+        for reply in replies {
+            let _ = reply.send(res.clone());
+        }
+    }
+
+    /// Serves one control request, `false` once the task should stop.
+    async fn handle(&mut self, control: Control) -> bool {
+        match control {
+            Control::Connect(reply) => {
+                let _ = reply.send(self.transport.connect().await);
+            }
+            Control::Notifications(reply) => {
+                let _ = reply.send(self.transport.notifications().await);
+            }
+            Control::Disconnect(reply) => {
+                let _ = reply.send(self.transport.disconnect().await);
+                return false;
+            }
+        }
+
+        true
+    }
+}
@@ -1,5 +1,8 @@
+use serde::Serialize;
+use std::str::FromStr;
+
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum BeltState {
     Undefined = 2,
     Static = 0,
@@ -16,13 +19,22 @@ impl From<u8> for BeltState {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Message {
     State(super::State),
+    Connection(ConnectionEvent),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected { reason: String },
+    Reconnecting { attempt: u32, delay_ms: u64 },
+    Closed,
 }
 
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum Mode {
     Undefined = 3,
     Standby = 2,
@@ -40,3 +52,85 @@ impl From<u8> for Mode {
         }
     }
 }
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sensitivity {
+    High = 1,
+    Medium = 2,
+    Low = 3,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    Metric = 0,
+    Imperial = 1,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    None = 0,
+    Distance = 1,
+    Calories = 2,
+    Time = 3,
+}
+
+impl From<u8> for Sensitivity {
+    fn from(i: u8) -> Self {
+        match i {
+            1 => Sensitivity::High,
+            2 => Sensitivity::Medium,
+            _ => Sensitivity::Low,
+        }
+    }
+}
+
+impl From<u8> for Units {
+    fn from(i: u8) -> Self {
+        match i {
+            1 => Units::Imperial,
+            _ => Units::Metric,
+        }
+    }
+}
+
+impl Units {
+    pub fn speed_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    pub fn distance_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "km",
+            Units::Imperial => "mi",
+        }
+    }
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "metric" => Ok(Units::Metric),
+            "imperial" => Ok(Units::Imperial),
+            _ => Err(format!("Unknown units {}, expected metric or imperial", s)),
+        }
+    }
+}
+
+impl From<u8> for Target {
+    fn from(i: u8) -> Self {
+        match i {
+            1 => Target::Distance,
+            2 => Target::Calories,
+            3 => Target::Time,
+            _ => Target::None,
+        }
+    }
+}
@@ -1,6 +1,59 @@
-use derive_more::{Display, Error as DError};
+use super::protocol::Command;
+use derive_more::{Display, Error as DError, From};
 
 #[derive(Display, Debug, DError)]
 pub struct MyError {
     pub details: String,
 }
+
+#[derive(Display, Debug, DError, Clone, Copy, PartialEq)]
+pub enum DecodeError {
+    #[display(fmt = "Frame too short, expected {} bytes, got {}", expected, actual)]
+    BadLength { expected: usize, actual: usize },
+    #[display(fmt = "Bad header byte {}", header)]
+    BadHeader { header: u8 },
+    #[display(fmt = "Bad trailer byte {}", trailer)]
+    BadTrailer { trailer: u8 },
+    #[display(fmt = "Bad CRC, expected {}, got {}", expected, actual)]
+    BadCrc { expected: u8, actual: u8 },
+    #[display(fmt = "Unknown packet type {}", kind)]
+    UnknownType { kind: u8 },
+    #[display(fmt = "Unexpected packet type {}", kind)]
+    UnexpectedType { kind: u8 },
+}
+
+/// What went wrong on the link to the pad, whatever the transport.
+#[derive(Display, Debug, DError, Clone, PartialEq)]
+pub enum TransportError {
+    #[display(fmt = "Not connected")]
+    NotConnected,
+    /// A stop came before the command was written.
+    #[display(fmt = "Cancelled by a stop")]
+    Cancelled,
+    #[display(fmt = "{}", details)]
+    Other { details: String },
+}
+
+impl From<btleplug::Error> for TransportError {
+    fn from(e: btleplug::Error) -> Self {
+        match e {
+            btleplug::Error::NotConnected => TransportError::NotConnected,
+            e => TransportError::Other {
+                details: e.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Display, Debug, DError, From)]
+pub enum VerifyError {
+    #[display(fmt = "{:?} not confirmed after {} attempts", command, attempts)]
+    #[from(ignore)]
+    Timeout {
+        #[error(not(source))]
+        command: Command,
+        attempts: u32,
+    },
+    #[display(fmt = "{}", _0)]
+    Transport(TransportError),
+}
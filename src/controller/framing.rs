@@ -0,0 +1,89 @@
+//! Reassembly of fe01 notifications into whole frames.
+//!
+//! BLE stacks with a small MTU split the longer frames over several
+//! notifications, and a notification is not guaranteed to start on a frame
+//! boundary. `FrameAssembler` buffers the bytes and hands out `0xF8 .. 0xFD`
+//! frames only once their CRC checks out, along with the bytes it gave up on.
+
+use super::protocol::{self, RESP_HEADER, TRAILER};
+use log::warn;
+
+/// Longest frame the pad sends, anything longer is garbage.
+const MAX_FRAME_LEN: usize = 64;
+
+/// What a notification completed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Assembled {
+    pub frames: Vec<Vec<u8>>,
+    /// Bytes that could not be part of a frame, such as frames with a bad CRC,
+    /// trailer or length. One entry per stretch of them.
+    pub dropped: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    buf: Vec<u8>,
+}
+
+impl FrameAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one notification and returns every frame it completed, and what
+    /// was dropped on the way.
+    pub fn push(&mut self, data: &[u8]) -> Assembled {
+        self.buf.extend_from_slice(data);
+
+        let mut assembled = Assembled::default();
+        while let Some((start, end)) = self.next_frame() {
+            assembled.dropped.extend(self.drop_garbage(start));
+            assembled
+                .frames
+                .push(self.buf.drain(..end - start).collect());
+        }
+
+        // Keep at most one partial frame around, starting at a header.
+        let start = self
+            .buf
+            .iter()
+            .enumerate()
+            .position(|(i, b)| *b == RESP_HEADER && self.buf.len() - i <= MAX_FRAME_LEN)
+            .unwrap_or(self.buf.len());
+        assembled.dropped.extend(self.drop_garbage(start));
+
+        assembled
+    }
+
+    fn drop_garbage(&mut self, len: usize) -> Option<Vec<u8>> {
+        if len == 0 {
+            return None;
+        }
+        warn!("Dropping {} bytes of garbage {:?}", len, &self.buf[..len]);
+        Some(self.buf.drain(..len).collect())
+    }
+
+    /// Bounds of the first frame completed in the buffer, preferring the shortest
+    /// one. The header and trailer bytes can also show up in the payload, so only
+    /// a CRC match makes a frame.
+    fn next_frame(&self) -> Option<(usize, usize)> {
+        let trailers = self
+            .buf
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == TRAILER)
+            .map(|(i, _)| i + 1);
+
+        for end in trailers {
+            let found = (end.saturating_sub(MAX_FRAME_LEN)..end)
+                .rev()
+                .filter(|start| self.buf[*start] == RESP_HEADER)
+                .find(|start| protocol::verify(&self.buf[*start..end], RESP_HEADER).is_ok());
+            if let Some(start) = found {
+                return Some((start, end));
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,110 @@
+//! Fan-out of decoded states and connection events to everyone interested in
+//! the pad.
+//!
+//! The latest state is kept in a `watch` so that new subscribers start from the
+//! current snapshot, and every update goes through a `broadcast`. A subscriber
+//! that falls behind skips straight to the latest snapshot instead of replaying
+//! stale states.
+
+use super::enums::{ConnectionEvent, Message};
+use super::State;
+
+use futures::stream::Stream;
+use log::warn;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+const CAPACITY: usize = 64;
+
+#[derive(Debug)]
+pub struct Hub {
+    latest: watch::Sender<Option<State>>,
+    connection: watch::Sender<ConnectionEvent>,
+    updates: broadcast::Sender<Message>,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self {
+            latest: watch::channel(None).0,
+            connection: watch::channel(ConnectionEvent::Connected).0,
+            updates: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Hub {
+    pub fn publish(&self, state: State) {
+        self.latest.send_replace(Some(state.clone()));
+        // Nobody listening is fine, the snapshot is kept anyway.
+        let _ = self.updates.send(Message::State(state));
+    }
+
+    pub fn publish_connection(&self, event: ConnectionEvent) {
+        self.connection.send_replace(event.clone());
+        let _ = self.updates.send(Message::Connection(event));
+    }
+
+    pub fn connection(&self) -> ConnectionEvent {
+        self.connection.borrow().clone()
+    }
+
+    pub fn latest(&self) -> Option<State> {
+        self.latest.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            snapshot: self.latest(),
+            latest: self.latest.subscribe(),
+            updates: self.updates.subscribe(),
+        }
+    }
+
+    /// Like `subscribe`, but only with the updates published from now on.
+    pub fn subscribe_updates(&self) -> Subscription {
+        Subscription {
+            snapshot: None,
+            latest: self.latest.subscribe(),
+            updates: self.updates.subscribe(),
+        }
+    }
+}
+
+pub struct Subscription {
+    snapshot: Option<State>,
+    latest: watch::Receiver<Option<State>>,
+    updates: broadcast::Receiver<Message>,
+}
+
+impl Subscription {
+    /// The latest snapshot first, then every update. `None` once the pad is gone.
+    pub async fn recv(&mut self) -> Option<Message> {
+        if let Some(state) = self.snapshot.take() {
+            return Some(Message::State(state));
+        }
+
+        loop {
+            match self.updates.recv().await {
+                Ok(message) => return Some(message),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber lagged behind by {} states", skipped);
+                    self.updates = self.updates.resubscribe();
+                    if let Some(state) = self.latest.borrow().clone() {
+                        return Some(Message::State(state));
+                    }
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Message> + Send {
+        futures::stream::unfold(self, |mut subscription| async move {
+            subscription
+                .recv()
+                .await
+                .map(|message| (message, subscription))
+        })
+    }
+}
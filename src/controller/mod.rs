@@ -1,292 +1,509 @@
 pub mod enums;
 use enums::*;
 
-mod error;
+pub mod error;
 use error::*;
 
-use log::{info, trace, warn};
+pub mod framing;
+use framing::FrameAssembler;
 
-use btleplug::api::{Characteristic, Peripheral, ValueNotification};
-use std::collections::BTreeSet;
-use std::error::Error;
-use std::ops::Sub;
+pub mod protocol;
+use protocol::{Command, Preference, Response};
 
-use futures::stream::Stream;
+pub mod units;
+use units::{Distance, Elapsed, Speed};
 
-use std::sync::Arc;
-use std::time::SystemTime;
-use tokio::sync::watch::Sender;
-use tokio::sync::{Mutex, RwLock};
+pub mod ramp;
+use ramp::{Ramp, Ramps};
 
+use log::{info, warn};
+
+use futures::stream::{Stream, StreamExt};
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+use serde::Serialize;
 use std::pin::Pin;
 
-const FE01: &str = "0000fe01";
-const FEO2: &str = "0000fe02";
-const MIN_TIME_BETWEEN_CMDS: u128 = 690;
+pub mod registry;
+
+pub mod hub;
+use hub::{Hub, Subscription};
+
+pub mod btle;
+pub mod transport;
+pub use btle::{BtleTransport, Rescan};
+pub use transport::{Notifications, Transport};
+
+mod connection;
+mod scheduler;
+mod supervisor;
+use connection::{Connection, Control, Reply, Write};
+
+const HISTORY_QUIET_TIME: u64 = 2000;
+const VERIFY_ATTEMPTS: u32 = 3;
+const VERIFY_TIMEOUT: u64 = 2000;
+const VERIFY_POLL: u64 = 750;
+/// How long the warm-up waits for the belt to start.
+const WARM_UP_WAIT: u64 = 10000;
 
-impl<T: Peripheral> Clone for Pad<T> {
+impl<T: Transport> Clone for Pad<T> {
     fn clone(&self) -> Self {
         Self {
-            char_fe01: self.char_fe01.clone(),
-            char_fe02: self.char_fe02.clone(),
-            peripheral: Arc::clone(&self.peripheral),
-            subscribers: Arc::clone(&self.subscribers),
-            last_time: Arc::clone(&self.last_time),
+            control: self.control.clone(),
+            commands: self.commands.clone(),
+            hub: Arc::clone(&self.hub),
+            malformed: Arc::clone(&self.malformed),
+            ramps: Arc::clone(&self.ramps),
+            targets: Arc::clone(&self.targets),
+            transport: PhantomData,
         }
     }
 }
+
+/// Cheap handle to the connection task owning the transport.
 #[derive(Debug)]
-pub struct Pad<T: Peripheral> {
-    char_fe01: Characteristic,
-    char_fe02: Characteristic,
-    peripheral: Arc<Mutex<T>>,
-    subscribers: Arc<RwLock<Vec<Sender<Message>>>>,
-    last_time: Arc<Mutex<u128>>,
+pub struct Pad<T: Transport> {
+    control: mpsc::UnboundedSender<Control>,
+    commands: mpsc::UnboundedSender<Write>,
+    hub: Arc<Hub>,
+    malformed: Arc<AtomicUsize>,
+    ramps: Arc<Mutex<Ramps>>,
+    /// Bumped for every new target of the belt, which cancels running ramps.
+    targets: Arc<AtomicUsize>,
+    transport: PhantomData<fn() -> T>,
 }
-impl<T: Peripheral> Pad<T> {
-    pub async fn new(peripheral: &T) -> Result<Pad<T>, Box<dyn Error>> {
-        let is_connected = peripheral.is_connected().await?;
-
-        if !is_connected {
-            let connected = peripheral.connect().await;
-            match connected {
-                Ok(_) => info!("Connected!"),
-                Err(e) => {
-                    return Err(Box::new(MyError {
-                        details: e.to_string(),
-                    }))
-                }
+impl<T: Transport> Pad<T> {
+    /// Connects over `transport` and starts supervising the connection.
+    pub async fn new(transport: T) -> Result<Pad<T>, TransportError> {
+        let (control, commands) = Connection::spawn(transport);
+
+        let pad = Pad {
+            control,
+            commands,
+            hub: Arc::new(Hub::default()),
+            malformed: Arc::new(AtomicUsize::new(0)),
+            ramps: Arc::new(Mutex::new(Ramps::default())),
+            targets: Arc::new(AtomicUsize::new(0)),
+            transport: PhantomData,
+        };
+
+        pad.request(Control::Connect).await?;
+        tokio::spawn(supervisor::supervise(pad.clone()));
+
+        Ok(pad)
+    }
+
+    /// Stops the belt. With a cool-down this returns right away and the belt
+    /// slows down before it stops, which is when it returns true.
+    pub async fn stop_belt(&self) -> Result<bool, TransportError> {
+        let moving = self.state().is_some_and(|state| !state.speed.is_zero());
+        match self.ramps().cool_down() {
+            Some(ramp) if moving => {
+                info!("Cooling down");
+                let pad = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = pad.ramp_to(Speed::ZERO, ramp).await {
+                        warn!("Cool-down failed: {}", e);
+                    }
+                });
+                Ok(true)
             }
+            _ => self.change_speed(Speed::ZERO).await.map(|_| false),
         }
+    }
 
-        if peripheral.characteristics().len() == 0 {
-            peripheral.discover_services().await?;
+    /// Starts the belt, then ramps up to the warm-up speed if there is one.
+    pub async fn start_belt(&self) -> Result<(), TransportError> {
+        info!("Starting belt");
+        self.new_target();
+        self.send(&Command::StartBelt).await?;
+        if let Some((speed, ramp)) = self.ramps().warm_up() {
+            self.warm_up(speed, ramp);
         }
+        Ok(())
+    }
 
-        let char01 = Pad::<T>::get_char(FE01, peripheral.characteristics()).await?;
-        let char02 = Pad::<T>::get_char(FEO2, peripheral.characteristics()).await?;
+    pub async fn switch_mode(&self, mode: Mode) -> Result<(), TransportError> {
+        info!("Switching mode");
+        self.send(&Command::SwitchMode(mode)).await
+    }
 
-        Ok(Pad {
-            char_fe01: char01,
-            char_fe02: char02,
-            peripheral: Arc::new(Mutex::new(peripheral.clone())),
-            subscribers: Arc::new(RwLock::new(vec![])),
-            last_time: Arc::new(Mutex::new(0)),
-        })
+    pub async fn change_speed(&self, speed: Speed) -> Result<(), TransportError> {
+        info!("Changing speed to {}", speed);
+        self.new_target();
+        self.send(&Command::ChangeSpeed(speed)).await
     }
 
-    pub async fn stop_belt(&self) -> Result<(), btleplug::Error> {
-        self.change_speed(0).await
+    /// Changes speed through the intermediate speeds of `ramp`, returning once
+    /// the last one is written. Another speed, start or stop cancels the ramp.
+    pub async fn ramp_to(&self, speed: Speed, ramp: Ramp) -> Result<(), TransportError> {
+        let target = self.new_target();
+        let from = self.state().map(|state| state.speed).unwrap_or_default();
+        let (interval, speeds) = ramp::plan(from, speed, ramp);
+        info!(
+            "Ramping from {} to {} in {} steps",
+            from,
+            speed,
+            speeds.len()
+        );
+
+        let start = tokio::time::Instant::now();
+        for (index, step) in speeds.into_iter().enumerate() {
+            tokio::time::sleep_until(start + interval * index as u32).await;
+            if self.targets.load(Ordering::SeqCst) != target {
+                info!("Ramp to {} cancelled", speed);
+                return Ok(());
+            }
+            self.send(&Command::ChangeSpeed(step)).await?;
+        }
+        Ok(())
     }
 
-    pub async fn start_belt(&self) -> Result<(), btleplug::Error> {
-        let cmd = [247, 162, 4, 1, 255, 253];
-        info!("Starting belt");
-        self.send(&cmd).await
+    /// Warm-up and cool-down of `start_belt` and `stop_belt`, shared by every
+    /// handle of the pad.
+    pub fn set_ramps(&self, ramps: Ramps) {
+        *self.ramps.lock().unwrap() = ramps;
     }
 
-    pub async fn switch_mode(&self, mode: Mode) -> Result<(), btleplug::Error> {
-        let m = mode as u8;
-        let cmd: [u8; 6] = [247, 162, 2, m, 255, 253];
-        info!("Switching mode");
-        self.send(&cmd).await
+    pub fn ramps(&self) -> Ramps {
+        *self.ramps.lock().unwrap()
     }
 
-    pub async fn change_speed(&self, speed: u8) -> Result<(), btleplug::Error> {
-        let cmd = [247, 162, 1, speed, 255, 253];
-        info!("Changing speed to {}", speed);
-        self.send(&cmd).await
+    /// Cancels running ramps and returns the new target number.
+    fn new_target(&self) -> usize {
+        self.targets.fetch_add(1, Ordering::SeqCst) + 1
     }
 
-    pub async fn disconnect(&self) {
-        info!("Disconnecting");
-        self.peripheral
-            .lock()
-            .await
-            .disconnect()
+    /// Waits for the belt to start and ramps up to `speed` unless something
+    /// else was asked for in the meantime.
+    fn warm_up(&self, speed: Speed, ramp: Ramp) {
+        let pad = self.clone();
+        let target = self.targets.load(Ordering::SeqCst);
+        tokio::spawn(async move {
+            let mut states = pad.register();
+            let started =
+                tokio::time::timeout(tokio::time::Duration::from_millis(WARM_UP_WAIT), async {
+                    while let Some(message) = states.recv().await {
+                        if matches!(message, Message::State(state) if !state.speed.is_zero()) {
+                            return true;
+                        }
+                    }
+                    false
+                })
+                .await;
+
+            if started != Ok(true) {
+                warn!("Belt did not start, no warm-up");
+            } else if pad.targets.load(Ordering::SeqCst) == target {
+                info!("Warming up to {}", speed);
+                if let Err(e) = pad.ramp_to(speed, ramp).await {
+                    warn!("Warm-up failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Like `stop_belt`, but at once and waits until the pad reports a speed of 0.
+    pub async fn stop_belt_verified(&self) -> Result<(), VerifyError> {
+        info!("Stopping belt (verified)");
+        self.new_target();
+        self.send_verified(Command::ChangeSpeed(Speed::ZERO), |state| {
+            state.speed.is_zero()
+        })
+        .await
+    }
+
+    /// Like `start_belt`, but without warm-up and waits until the pad reports a moving belt.
+    pub async fn start_belt_verified(&self) -> Result<(), VerifyError> {
+        info!("Starting belt (verified)");
+        self.new_target();
+        self.send_verified(Command::StartBelt, |state| {
+            state.belt_state == BeltState::Moving
+        })
+        .await
+    }
+
+    /// Like `switch_mode`, but waits until the pad reports the new mode.
+    pub async fn switch_mode_verified(&self, mode: Mode) -> Result<(), VerifyError> {
+        info!("Switching mode (verified)");
+        self.send_verified(Command::SwitchMode(mode), move |state| state.mode == mode)
             .await
-            .expect("Error disconnecting from BLE peripheral");
     }
 
-    pub async fn services(&self) {
-        info!("Discover peripheral services...");
-        for service in self.peripheral.lock().await.services() {
-            info!(
-                "Service UUID {}, primary: {}",
-                service.uuid, service.primary
-            );
-            for characteristic in service.characteristics {
-                info!("  {:?}", characteristic);
+    /// Like `change_speed`, but waits until the belt reaches the new speed.
+    pub async fn change_speed_verified(&self, speed: Speed) -> Result<(), VerifyError> {
+        info!("Changing speed to {} (verified)", speed);
+        self.new_target();
+        self.send_verified(Command::ChangeSpeed(speed), move |state| {
+            state.speed == speed
+        })
+        .await
+    }
+
+    /// Sends the command and polls the status until `confirmed` holds. The command
+    /// is resent up to `VERIFY_ATTEMPTS` times, doubling the wait every time. Only
+    /// the states received once the command is written can confirm it.
+    async fn send_verified(
+        &self,
+        command: Command,
+        confirmed: impl Fn(&State) -> bool,
+    ) -> Result<(), VerifyError> {
+        let mut wait = tokio::time::Duration::from_millis(VERIFY_TIMEOUT);
+
+        for attempt in 1..=VERIFY_ATTEMPTS {
+            self.send(&command).await?;
+            let mut states = self.hub.subscribe_updates();
+
+            let deadline = tokio::time::sleep(wait);
+            tokio::pin!(deadline);
+            let mut poll = tokio::time::interval(tokio::time::Duration::from_millis(VERIFY_POLL));
+
+            loop {
+                tokio::select! {
+                    message = states.recv() => match message {
+                        Some(Message::State(state)) if confirmed(&state) => return Ok(()),
+                        Some(_) => {}
+                        None => return Err(TransportError::NotConnected.into()),
+                    },
+                    _ = poll.tick() => self.ask_stats().await?,
+                    _ = &mut deadline => break,
+                }
             }
+
+            warn!(
+                "{:?} not confirmed after attempt {}/{}",
+                command, attempt, VERIFY_ATTEMPTS
+            );
+            wait *= 2;
         }
+
+        Err(VerifyError::Timeout {
+            command,
+            attempts: VERIFY_ATTEMPTS,
+        })
     }
 
-    pub async fn register(self, chan: Sender<Message>) {
-        self.subscribers.write().await.push(chan);
+    pub async fn disconnect(&self) -> Result<(), TransportError> {
+        info!("Disconnecting");
+        self.request(Control::Disconnect).await
     }
 
-    pub async fn subs(&self) -> Result<(), btleplug::Error> {
-        info!("Subscribing");
-        self.peripheral
-            .lock()
-            .await
-            .subscribe(&self.char_fe01)
-            .await
+    /// Live states of the pad, starting with the latest one if there is any.
+    pub fn register(&self) -> Subscription {
+        self.hub.subscribe()
+    }
+
+    /// Latest state reported by the pad.
+    pub fn state(&self) -> Option<State> {
+        self.hub.latest()
+    }
+
+    /// Latest connection event, see `register` for all of them.
+    pub fn connection(&self) -> ConnectionEvent {
+        self.hub.connection()
+    }
+
+    /// Whether the connection task is gone, i.e. the pad was disconnected.
+    fn is_closed(&self) -> bool {
+        self.control.is_closed()
+    }
+
+    pub async fn gets(&self) -> Result<Notifications, TransportError> {
+        self.request(Control::Notifications).await
     }
 
-    pub async fn gets(
+    /// Whole frames reassembled from the notifications. The bytes dropped on
+    /// the way are counted in `malformed_frames`.
+    pub async fn frames(
         &self,
-    ) -> Result<Pin<Box<dyn Stream<Item = ValueNotification> + Send>>, btleplug::Error> {
-        self.peripheral.lock().await.notifications().await
+    ) -> Result<Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>, TransportError> {
+        let malformed = Arc::clone(&self.malformed);
+        let frames = self
+            .gets()
+            .await?
+            .scan(FrameAssembler::new(), move |assembler, data| {
+                let assembled = assembler.push(&data);
+                malformed.fetch_add(assembled.dropped.len(), Ordering::Relaxed);
+                futures::future::ready(Some(futures::stream::iter(assembled.frames)))
+            })
+            .flatten();
+
+        Ok(Box::pin(frames))
     }
 
-    pub async fn ask_stats(&self) -> Result<(), btleplug::Error> {
-        let cmd: [u8; 6] = [247, 162, 0, 0, 162, 253];
-        info!("Asking stats");
-        self.send(&cmd).await
-    }
-
-    async fn send(&self, msg: &[u8]) -> Result<(), btleplug::Error> {
-        let cmd = [
-            msg[..msg.len() - 2].to_vec(),
-            [Pad::<T>::crc(msg), msg[msg.len() - 1]].to_vec(),
-        ]
-        .concat();
-
-        let device = self.peripheral.lock().await;
-        let mut last_time = self.last_time.lock().await;
-        info!("OLD last_time {}", *last_time);
-
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-
-        if *last_time != 0 {
-            match now.checked_sub(*last_time) {
-                Some(already_waited) => {
-                    info!("Already waited {}", already_waited);
-                    if already_waited < MIN_TIME_BETWEEN_CMDS {
-                        info!("Sleeping for {}", MIN_TIME_BETWEEN_CMDS - already_waited);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(
-                            (MIN_TIME_BETWEEN_CMDS - already_waited) as u64,
-                        ))
-                        .await
-                    }
-                }
-                None => {
-                    info!("Sleeping for {}", MIN_TIME_BETWEEN_CMDS);
-                    tokio::time::sleep(tokio::time::Duration::from_millis(
-                        (MIN_TIME_BETWEEN_CMDS) as u64,
-                    ))
-                    .await
+    /// Frames decoded into responses. Malformed frames are logged, counted in
+    /// `malformed_frames` and skipped.
+    pub async fn responses(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = Response> + Send>>, TransportError> {
+        let malformed = Arc::clone(&self.malformed);
+        let responses = self.frames().await?.filter_map(move |frame| {
+            let res = match Response::decode(&frame) {
+                Ok(response) => Some(response),
+                Err(e) => {
+                    let count = malformed.fetch_add(1, Ordering::Relaxed) + 1;
+                    warn!("Malformed frame #{} {:?}: {}", count, frame, e);
+                    None
                 }
-            }
-        } else {
-            info!("Not sleeping for the first command");
-        }
+            };
+            futures::future::ready(res)
+        });
 
-        *last_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+        Ok(Box::pin(responses))
+    }
 
-        info!("NEW last_time {}", *last_time);
+    /// Number of malformed frames received so far.
+    pub fn malformed_frames(&self) -> usize {
+        self.malformed.load(Ordering::Relaxed)
+    }
 
-        device
-            .write(
-                &self.char_fe02,
-                &cmd,
-                btleplug::api::WriteType::WithoutResponse,
-            )
-            .await
+    pub async fn ask_stats(&self) -> Result<(), TransportError> {
+        info!("Asking stats");
+        self.send(&Command::AskStats).await
+    }
+
+    /// Queues the command in the connection task and waits until it is written.
+    /// Dropping the returned future before that cancels the command.
+    async fn send(&self, command: &Command) -> Result<(), TransportError> {
+        let (reply, rx) = oneshot::channel();
+        self.commands
+            .send(Write {
+                command: *command,
+                reply,
+            })
+            .map_err(|_| TransportError::NotConnected)?;
+        rx.await.map_err(|_| TransportError::NotConnected)?
+    }
+
+    async fn request<R>(&self, control: fn(Reply<R>) -> Control) -> Result<R, TransportError> {
+        let (reply, rx) = oneshot::channel();
+        self.control
+            .send(control(reply))
+            .map_err(|_| TransportError::NotConnected)?;
+        rx.await.map_err(|_| TransportError::NotConnected)?
+    }
+
+    /// Maximal belt speed the pad allows.
+    pub async fn set_max_speed(&self, speed: Speed) -> Result<(), TransportError> {
+        info!("Setting max speed to {}", speed);
+        self.set_pref(Preference::MaxSpeed(speed)).await
+    }
+
+    /// Speed the belt starts at.
+    pub async fn set_start_speed(&self, speed: Speed) -> Result<(), TransportError> {
+        info!("Setting start speed to {}", speed);
+        self.set_pref(Preference::StartSpeed(speed)).await
+    }
+
+    /// Automatic start of the belt when someone steps on it (intelligent mode).
+    pub async fn set_auto_start(&self, enabled: bool) -> Result<(), TransportError> {
+        info!("Setting auto start to {}", enabled);
+        self.set_pref(Preference::AutoStart(enabled)).await
+    }
+
+    pub async fn set_sensitivity(&self, sensitivity: Sensitivity) -> Result<(), TransportError> {
+        info!("Setting sensitivity to {:?}", sensitivity);
+        self.set_pref(Preference::Sensitivity(sensitivity)).await
+    }
+
+    pub async fn set_units(&self, units: Units) -> Result<(), TransportError> {
+        info!("Setting display units to {:?}", units);
+        self.set_pref(Preference::Units(units)).await
+    }
+
+    pub async fn set_child_lock(&self, enabled: bool) -> Result<(), TransportError> {
+        info!("Setting child lock to {}", enabled);
+        self.set_pref(Preference::ChildLock(enabled)).await
+    }
+
+    /// Target shown on the pad display. `value` is in metres, kcal or seconds
+    /// depending on `target`, and is ignored for `Target::None`.
+    pub async fn set_target(&self, target: Target, value: u32) -> Result<(), TransportError> {
+        info!("Setting target to {:?} {}", target, value);
+        self.set_pref(Preference::Target(target, value)).await
     }
 
-    pub async fn ask_profile(&self) -> Result<(), btleplug::Error> {
-        let cmd = [247, 165, 96, 74, 77, 147, 113, 41, 201, 253];
-        self.send(&cmd).await
+    pub async fn ask_history(&self, index: u8) -> Result<(), TransportError> {
+        info!("Asking history {}", index);
+        self.send(&Command::AskHistory(index)).await
     }
 
-    async fn get_char(
-        match_str: &str,
-        characteristics: BTreeSet<Characteristic>,
-    ) -> Result<Characteristic, Box<dyn Error>> {
-        for char in characteristics.iter() {
-            if char.uuid.to_string().contains(match_str) {
-                return Ok(char.clone());
+    /// Requests the records of the last sessions stored on the pad and collects
+    /// replies until no record arrives for `HISTORY_QUIET_TIME` ms. Status
+    /// frames from the polling in between do not count.
+    pub async fn fetch_history(&self) -> Result<Vec<HistoryRecord>, TransportError> {
+        let mut responses = self.responses().await?;
+        self.ask_history(0).await?;
+
+        let quiet_time = tokio::time::Duration::from_millis(HISTORY_QUIET_TIME);
+        let quiet = tokio::time::sleep(quiet_time);
+        tokio::pin!(quiet);
+
+        let mut records = vec![];
+        loop {
+            tokio::select! {
+                data = responses.next() => match data {
+                    Some(Response::History(record)) => {
+                        info!("Received history record {:?}", record);
+                        records.push(record);
+                        quiet.as_mut().reset(tokio::time::Instant::now() + quiet_time);
+                    }
+                    Some(_) => {}
+                    None => break,
+                },
+                _ = &mut quiet => break,
             }
         }
 
-        Err(Box::new(MyError {
-            details: "Not found".to_string(),
-        }))
+        Ok(records)
     }
 
-    fn crc(cmd: &[u8]) -> u8 {
-        cmd[1..cmd.len() - 2]
-            .to_vec()
-            .iter()
-            .fold(0, |acc: u32, val| acc + *val as u32) as u8
+    pub async fn ask_profile(&self) -> Result<(), TransportError> {
+        self.send(&Command::AskProfile).await
     }
 
-    // fn set_pref_arr(key: u8, arr: &[u8]) -> [u8] {
-    //     arr.it
-    //     let ar: [u32] = vec![8].try_into().unwrap_or([0]);
-
-    //     [[&[247, 166, key], arr].concat(), [172, 253]].concat()
-    // }
+    async fn set_pref(&self, pref: Preference) -> Result<(), TransportError> {
+        self.send(&Command::SetPreference(pref)).await
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct State {
     pub belt_state: BeltState,
-    pub speed: usize,
+    pub speed: Speed,
     pub mode: Mode,
-    pub time: usize,
-    pub distance: usize,
+    pub time: Elapsed,
+    pub distance: Distance,
     pub steps: usize,
-    pub last_speed: usize,
+    pub last_speed: Speed,
 }
 
 impl State {
-    fn convert(data: &[u8]) -> usize {
-        let mut value: usize = 0;
-        for i in 0..data.len() {
-            value += ((data[i] as usize) << (8 * (data.len() - 1 - i))) as usize;
-        }
-
-        value
-    }
-    fn check_data(data: &Vec<u8>) -> bool {
-        if data.len() < 2 {
-            false
-        } else if data[0] == 248 && data[1] == 162 {
-            true
-        } else {
-            false
+    pub fn new(data: Vec<u8>) -> Result<Self, DecodeError> {
+        match Response::decode(&data)? {
+            Response::Status(state) => Ok(state),
+            _ => Err(DecodeError::UnexpectedType { kind: data[1] }),
         }
     }
-    pub fn new(data: Vec<u8>) -> Option<Self> {
-        if State::check_data(&data) {
-            let belt_state: BeltState = data[2].into();
-            let speed: usize = data[3].into();
-            let mode: Mode = data[4].into();
-            let time: usize = State::convert(&data[5..8]);
-            let distance: usize = State::convert(&data[8..11]);
-            let steps: usize = State::convert(&data[11..14]);
-            let last_speed: usize = data[14].into();
-
-            Some(Self {
-                belt_state,
-                speed,
-                mode,
-                time,
-                distance,
-                steps,
-                last_speed,
-            })
-        } else {
-            None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRecord {
+    pub index: u8,
+    pub time: Elapsed,
+    pub distance: Distance,
+    pub steps: usize,
+}
+
+impl HistoryRecord {
+    pub fn new(data: Vec<u8>) -> Result<Self, DecodeError> {
+        match Response::decode(&data)? {
+            Response::History(record) => Ok(record),
+            _ => Err(DecodeError::UnexpectedType { kind: data[1] }),
         }
     }
 }
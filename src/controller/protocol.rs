@@ -0,0 +1,268 @@
+//! Wire format of the WalkingPad.
+//!
+//! Commands are written to fe02 as `[247, kind, payload.., crc, 253]` and the pad
+//! answers on fe01 with `[248, kind, payload.., crc, 253]`. The CRC is the low
+//! byte of the sum of everything between the header and the CRC itself.
+
+use super::enums::*;
+use super::error::DecodeError;
+use super::units::{Distance, Elapsed, Speed};
+use super::{HistoryRecord, State};
+
+pub const CMD_HEADER: u8 = 247;
+pub const RESP_HEADER: u8 = 248;
+pub const TRAILER: u8 = 253;
+
+const KIND_STATUS: u8 = 162;
+const KIND_PROFILE: u8 = 165;
+const KIND_PREF: u8 = 166;
+const KIND_HISTORY: u8 = 167;
+
+const STATUS_STATS: u8 = 0;
+const STATUS_SPEED: u8 = 1;
+const STATUS_MODE: u8 = 2;
+const STATUS_START: u8 = 4;
+
+const HISTORY_ASK: u8 = 170;
+const PROFILE: [u8; 6] = [96, 74, 77, 147, 113, 41];
+
+const PREF_TARGET: u8 = 1;
+const PREF_AUTO_START: u8 = 2;
+const PREF_MAX_SPEED: u8 = 3;
+const PREF_START_SPEED: u8 = 4;
+const PREF_CHILD_LOCK: u8 = 5;
+const PREF_SENSITIVITY: u8 = 6;
+const PREF_UNITS: u8 = 8;
+
+const MIN_LEN: usize = 4;
+const STATUS_LEN: usize = 20;
+const STATUS_MIN_LEN: usize = 17;
+const HISTORY_LEN: usize = 19;
+const PREF_LEN: usize = 9;
+/// Metres per unit of the distance counters.
+const DISTANCE_UNIT: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preference {
+    /// Target in metres, kcal or seconds depending on the `Target`.
+    Target(Target, u32),
+    AutoStart(bool),
+    MaxSpeed(Speed),
+    StartSpeed(Speed),
+    ChildLock(bool),
+    Sensitivity(Sensitivity),
+    Units(Units),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    AskStats,
+    AskProfile,
+    AskHistory(u8),
+    /// `Speed::ZERO` stops the belt.
+    ChangeSpeed(Speed),
+    SwitchMode(Mode),
+    StartBelt,
+    SetPreference(Preference),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Status(State),
+    History(HistoryRecord),
+}
+
+/// Low byte of the sum of the bytes between the header and the CRC.
+pub fn crc(frame: &[u8]) -> u8 {
+    frame[1..frame.len() - 2]
+        .iter()
+        .fold(0u8, |acc, val| acc.wrapping_add(*val))
+}
+
+/// Checks length, header, trailer and CRC of a whole frame.
+pub fn verify(frame: &[u8], header: u8) -> Result<(), DecodeError> {
+    check_len(frame, MIN_LEN)?;
+    if frame[0] != header {
+        return Err(DecodeError::BadHeader { header: frame[0] });
+    }
+
+    let trailer = frame[frame.len() - 1];
+    if trailer != TRAILER {
+        return Err(DecodeError::BadTrailer { trailer });
+    }
+
+    let expected = crc(frame);
+    let actual = frame[frame.len() - 2];
+    if expected != actual {
+        return Err(DecodeError::BadCrc { expected, actual });
+    }
+
+    Ok(())
+}
+
+fn check_len(frame: &[u8], expected: usize) -> Result<(), DecodeError> {
+    if frame.len() < expected {
+        Err(DecodeError::BadLength {
+            expected,
+            actual: frame.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn byte2int(data: &[u8]) -> usize {
+    data.iter().fold(0, |acc, val| (acc << 8) | *val as usize)
+}
+
+pub fn int2byte(val: u32, width: usize) -> Vec<u8> {
+    (0..width)
+        .map(|i| (val >> (8 * (width - 1 - i))) as u8)
+        .collect()
+}
+
+fn frame(header: u8, kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = [&[header, kind], payload, &[0, TRAILER]].concat();
+    let len = frame.len();
+    frame[len - 2] = crc(&frame);
+    frame
+}
+
+impl Preference {
+    /// `[key, type, value]` with the value on 3 bytes. The type is the kind of
+    /// target for `Target` and 0 for everything else.
+    fn encode(&self) -> Vec<u8> {
+        match *self {
+            Preference::Target(target, value) => Preference::int(PREF_TARGET, target as u8, value),
+            Preference::AutoStart(on) => Preference::int(PREF_AUTO_START, 0, on as u32),
+            Preference::MaxSpeed(speed) => {
+                Preference::int(PREF_MAX_SPEED, 0, speed.tenths_kmh().into())
+            }
+            Preference::StartSpeed(speed) => {
+                Preference::int(PREF_START_SPEED, 0, speed.tenths_kmh().into())
+            }
+            Preference::ChildLock(on) => Preference::int(PREF_CHILD_LOCK, 0, on as u32),
+            Preference::Sensitivity(s) => Preference::int(PREF_SENSITIVITY, 0, s as u32),
+            Preference::Units(u) => Preference::int(PREF_UNITS, 0, u as u32),
+        }
+    }
+
+    fn int(key: u8, kind: u8, val: u32) -> Vec<u8> {
+        [vec![key, kind], int2byte(val, 3)].concat()
+    }
+
+    /// Parses the preference out of a whole, already verified 0xA6 frame.
+    fn decode(frame: &[u8]) -> Result<Preference, DecodeError> {
+        check_len(frame, PREF_LEN)?;
+        let (key, kind) = (frame[2], frame[3]);
+        let val = byte2int(&frame[4..7]);
+        match key {
+            PREF_TARGET => Ok(Preference::Target(Target::from(kind), val as u32)),
+            PREF_AUTO_START => Ok(Preference::AutoStart(val != 0)),
+            PREF_MAX_SPEED => Ok(Preference::MaxSpeed(Speed::from_tenths_kmh(val as u8))),
+            PREF_START_SPEED => Ok(Preference::StartSpeed(Speed::from_tenths_kmh(val as u8))),
+            PREF_CHILD_LOCK => Ok(Preference::ChildLock(val != 0)),
+            PREF_SENSITIVITY => Ok(Preference::Sensitivity(Sensitivity::from(val as u8))),
+            PREF_UNITS => Ok(Preference::Units(Units::from(val as u8))),
+            _ => Err(DecodeError::UnknownType { kind: key }),
+        }
+    }
+}
+
+impl Command {
+    /// Whole frame including the CRC, ready to be written to fe02.
+    pub fn encode(&self) -> Vec<u8> {
+        match *self {
+            Command::AskStats => frame(CMD_HEADER, KIND_STATUS, &[STATUS_STATS, 0]),
+            Command::AskProfile => frame(CMD_HEADER, KIND_PROFILE, &PROFILE),
+            Command::AskHistory(index) => frame(CMD_HEADER, KIND_HISTORY, &[HISTORY_ASK, index]),
+            Command::ChangeSpeed(speed) => {
+                frame(CMD_HEADER, KIND_STATUS, &[STATUS_SPEED, speed.tenths_kmh()])
+            }
+            Command::SwitchMode(mode) => frame(CMD_HEADER, KIND_STATUS, &[STATUS_MODE, mode as u8]),
+            Command::StartBelt => frame(CMD_HEADER, KIND_STATUS, &[STATUS_START, 1]),
+            Command::SetPreference(pref) => frame(CMD_HEADER, KIND_PREF, &pref.encode()),
+        }
+    }
+
+    /// Parses a frame written to fe02.
+    pub fn decode(frame: &[u8]) -> Result<Command, DecodeError> {
+        verify(frame, CMD_HEADER)?;
+        let kind = frame[1];
+        let payload = &frame[2..frame.len() - 2];
+
+        match (kind, payload) {
+            (KIND_STATUS, [STATUS_STATS, _]) => Ok(Command::AskStats),
+            (KIND_STATUS, [STATUS_SPEED, speed]) => {
+                Ok(Command::ChangeSpeed(Speed::from_tenths_kmh(*speed)))
+            }
+            (KIND_STATUS, [STATUS_MODE, mode]) => Ok(Command::SwitchMode(Mode::from(*mode))),
+            (KIND_STATUS, [STATUS_START, _]) => Ok(Command::StartBelt),
+            (KIND_PROFILE, _) => Ok(Command::AskProfile),
+            (KIND_HISTORY, [HISTORY_ASK, index]) => Ok(Command::AskHistory(*index)),
+            (KIND_PREF, _) => Preference::decode(frame).map(Command::SetPreference),
+            _ => Err(DecodeError::UnknownType { kind }),
+        }
+    }
+}
+
+impl Response {
+    /// Whole frame as the pad sends it on fe01.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Response::Status(state) => {
+                let mut payload = vec![
+                    state.belt_state as u8,
+                    state.speed.tenths_kmh(),
+                    state.mode as u8,
+                ];
+                payload.extend(int2byte(state.time.secs(), 3));
+                payload.extend(int2byte(state.distance.metres() / DISTANCE_UNIT, 3));
+                payload.extend(int2byte(state.steps as u32, 3));
+                payload.push(state.last_speed.tenths_kmh());
+                payload.resize(STATUS_LEN - 4, 0);
+                frame(RESP_HEADER, KIND_STATUS, &payload)
+            }
+            Response::History(record) => {
+                let mut payload = vec![HISTORY_ASK, record.index, 0, 0, 0, 0];
+                payload.extend(int2byte(record.time.secs(), 3));
+                payload.extend(int2byte(record.distance.metres() / DISTANCE_UNIT, 3));
+                payload.extend(int2byte(record.steps as u32, 3));
+                frame(RESP_HEADER, KIND_HISTORY, &payload)
+            }
+        }
+    }
+
+    /// Parses a frame received on fe01. Every field is bounds-checked, so a short
+    /// or corrupted notification ends up as an error rather than a panic.
+    pub fn decode(frame: &[u8]) -> Result<Response, DecodeError> {
+        verify(frame, RESP_HEADER)?;
+
+        match frame[1] {
+            KIND_STATUS => {
+                check_len(frame, STATUS_MIN_LEN)?;
+                Ok(Response::Status(State {
+                    belt_state: frame[2].into(),
+                    speed: Speed::from_tenths_kmh(frame[3]),
+                    mode: frame[4].into(),
+                    time: Elapsed::from_secs(byte2int(&frame[5..8]) as u32),
+                    distance: Distance::from_metres(byte2int(&frame[8..11]) as u32 * DISTANCE_UNIT),
+                    steps: byte2int(&frame[11..14]),
+                    last_speed: Speed::from_tenths_kmh(frame[14]),
+                }))
+            }
+            KIND_HISTORY => {
+                check_len(frame, HISTORY_LEN)?;
+                Ok(Response::History(HistoryRecord {
+                    index: frame[3],
+                    time: Elapsed::from_secs(byte2int(&frame[8..11]) as u32),
+                    distance: Distance::from_metres(
+                        byte2int(&frame[11..14]) as u32 * DISTANCE_UNIT,
+                    ),
+                    steps: byte2int(&frame[14..17]),
+                }))
+            }
+            kind => Err(DecodeError::UnknownType { kind }),
+        }
+    }
+}
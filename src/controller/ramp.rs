@@ -0,0 +1,73 @@
+//! Speed changes spread over time.
+//!
+//! A ramp breaks a change of speed into intermediate speeds. They are written
+//! at most every other `MIN_TIME_BETWEEN_CMDS`, the status polls take the
+//! slots in between. Any new target for the belt cancels the ramp in progress.
+
+use super::connection::MIN_TIME_BETWEEN_CMDS;
+use super::units::Speed;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Shortest time between two speeds of a ramp.
+const STEP_TIME: Duration = MIN_TIME_BETWEEN_CMDS.saturating_mul(2);
+
+/// How `Pad::ramp_to` gets to the new speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ramp {
+    /// Evenly over the duration.
+    Over(Duration),
+    /// By at most this much per command.
+    Step(Speed),
+}
+
+/// Ramps applied by `Pad::start_belt` and `Pad::stop_belt`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ramps {
+    /// Speed the belt warms up to once started, it stays at the start speed without.
+    pub warm_up_to: Option<Speed>,
+    /// Seconds the warm-up lasts.
+    #[serde(default)]
+    pub warm_up_secs: u64,
+    /// Seconds the belt slows down for before it stops, 0 stops it at once.
+    #[serde(default)]
+    pub cool_down_secs: u64,
+}
+
+impl Ramps {
+    pub fn warm_up(&self) -> Option<(Speed, Ramp)> {
+        self.warm_up_to
+            .map(|speed| (speed, Ramp::Over(Duration::from_secs(self.warm_up_secs))))
+    }
+
+    pub fn cool_down(&self) -> Option<Ramp> {
+        match self.cool_down_secs {
+            0 => None,
+            secs => Some(Ramp::Over(Duration::from_secs(secs))),
+        }
+    }
+}
+
+/// The speeds to write one after the other, ending with `to`, and the time
+/// between them.
+pub(crate) fn plan(from: Speed, to: Speed, ramp: Ramp) -> (Duration, Vec<Speed>) {
+    let from = from.tenths_kmh() as i32;
+    let delta = to.tenths_kmh() as i32 - from;
+    let changes = delta.unsigned_abs().max(1);
+
+    let (count, interval) = match ramp {
+        Ramp::Over(duration) => {
+            let slots = (duration.as_millis() / STEP_TIME.as_millis()) as u32;
+            let count = slots.clamp(1, changes);
+            (count, duration / count)
+        }
+        Ramp::Step(step) => (changes.div_ceil(step.tenths_kmh().max(1) as u32), STEP_TIME),
+    };
+
+    let speeds = (1..=count as i32)
+        .map(|index| Speed::from_tenths_kmh((from + delta * index / count as i32) as u8))
+        .collect();
+    (interval.max(STEP_TIME), speeds)
+}
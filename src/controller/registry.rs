@@ -0,0 +1,49 @@
+//! Named pads driven by one daemon.
+
+use super::{Pad, Transport};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Registry<T: Transport> {
+    pads: Arc<BTreeMap<String, Pad<T>>>,
+}
+
+impl<T: Transport> Clone for Registry<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pads: Arc::clone(&self.pads),
+        }
+    }
+}
+
+impl<T: Transport> Default for Registry<T> {
+    fn default() -> Self {
+        Self {
+            pads: Arc::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<T: Transport> Registry<T> {
+    pub fn insert(&mut self, id: String, pad: Pad<T>) {
+        Arc::make_mut(&mut self.pads).insert(id, pad);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Pad<T>> {
+        self.pads.get(id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.pads.keys().cloned().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Pad<T>)> {
+        self.pads.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pads.is_empty()
+    }
+}
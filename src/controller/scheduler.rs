@@ -0,0 +1,198 @@
+//! Queue of commands waiting for their write slot.
+//!
+//! Only the latest speed and mode change matter, so a queued one is updated in
+//! place instead of queueing another. A stop jumps ahead of everything and
+//! drops the queued speed changes, as well as the queued commands that would
+//! set the belt going again, whose callers get `TransportError::Cancelled`. At
+//! most one `AskStats` is queued. The callers of merged commands all get the
+//! result of the single write.
+
+use super::connection::{Reply, Write};
+use super::enums::Mode;
+use super::error::TransportError;
+use super::protocol::Command;
+
+use log::info;
+use std::collections::VecDeque;
+
+struct Entry {
+    command: Command,
+    replies: Vec<Reply<()>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    queue: VecDeque<Entry>,
+}
+
+impl Scheduler {
+    pub fn push(&mut self, write: Write) {
+        let Write { command, reply } = write;
+
+        match command {
+            Command::ChangeSpeed(speed) if speed.is_zero() => {
+                let mut replies = vec![reply];
+                self.queue.retain_mut(|entry| match entry.command {
+                    Command::ChangeSpeed(_) => {
+                        info!("Stop supersedes queued {:?}", entry.command);
+                        replies.append(&mut entry.replies);
+                        false
+                    }
+                    Command::StartBelt | Command::SwitchMode(Mode::Automat) => {
+                        info!("Stop cancels queued {:?}", entry.command);
+                        for reply in entry.replies.drain(..) {
+                            let _ = reply.send(Err(TransportError::Cancelled));
+                        }
+                        false
+                    }
+                    _ => true,
+                });
+                self.queue.push_front(Entry { command, replies });
+            }
+            Command::ChangeSpeed(_) => self.merge(
+                command,
+                reply,
+                |queued| matches!(queued, Command::ChangeSpeed(speed) if !speed.is_zero()),
+            ),
+            Command::SwitchMode(_) => self.merge(command, reply, |queued| {
+                matches!(queued, Command::SwitchMode(_))
+            }),
+            Command::AskStats => {
+                self.merge(command, reply, |queued| matches!(queued, Command::AskStats))
+            }
+            _ => self.queue.push_back(Entry {
+                command,
+                replies: vec![reply],
+            }),
+        }
+    }
+
+    /// Next command to write along with everyone waiting for it. Commands
+    /// nobody waits for anymore are skipped.
+    pub fn pop(&mut self) -> Option<(Command, Vec<Reply<()>>)> {
+        while let Some(entry) = self.queue.pop_front() {
+            let replies: Vec<_> = entry
+                .replies
+                .into_iter()
+                .filter(|reply| !reply.is_closed())
+                .collect();
+
+            if replies.is_empty() {
+                info!("Dropping cancelled {:?}", entry.command);
+            } else {
+                return Some((entry.command, replies));
+            }
+        }
+
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Replaces the queued command matching `same` with `command`, or queues it.
+    fn merge(&mut self, command: Command, reply: Reply<()>, same: impl Fn(&Command) -> bool) {
+        match self.queue.iter_mut().find(|entry| same(&entry.command)) {
+            Some(entry) => {
+                if entry.command != command {
+                    info!("Coalescing {:?} into {:?}", entry.command, command);
+                }
+                entry.command = command;
+                entry.replies.push(reply);
+            }
+            None => self.queue.push_back(Entry {
+                command,
+                replies: vec![reply],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::units::Speed;
+    use tokio::sync::oneshot;
+
+    fn push(
+        scheduler: &mut Scheduler,
+        command: Command,
+    ) -> oneshot::Receiver<Result<(), TransportError>> {
+        let (reply, rx) = oneshot::channel();
+        scheduler.push(Write { command, reply });
+        rx
+    }
+
+    fn speed(tenths_kmh: u8) -> Command {
+        Command::ChangeSpeed(Speed::from_tenths_kmh(tenths_kmh))
+    }
+
+    #[test]
+    fn collapses_speed_changes_to_the_latest() {
+        let mut scheduler = Scheduler::default();
+        let waiting: Vec<_> = [20, 30, 40]
+            .into_iter()
+            .map(|tenths| push(&mut scheduler, speed(tenths)))
+            .collect();
+
+        let (command, replies) = scheduler.pop().unwrap();
+        assert_eq!(command, speed(40));
+        assert_eq!(replies.len(), waiting.len());
+        assert!(scheduler.pop().is_none());
+    }
+
+    #[test]
+    fn stop_jumps_ahead_of_speed_changes() {
+        let mut scheduler = Scheduler::default();
+        let _stats = push(&mut scheduler, Command::AskStats);
+        let _speed = push(&mut scheduler, speed(40));
+        let _stop = push(&mut scheduler, speed(0));
+
+        let (command, replies) = scheduler.pop().unwrap();
+        assert_eq!(command, speed(0));
+        assert_eq!(replies.len(), 2);
+        assert_eq!(scheduler.pop().unwrap().0, Command::AskStats);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn stop_cancels_a_queued_start() {
+        let mut scheduler = Scheduler::default();
+        let mut start = push(&mut scheduler, Command::StartBelt);
+        let _stop = push(&mut scheduler, speed(0));
+
+        let (command, replies) = scheduler.pop().unwrap();
+        assert_eq!(command, speed(0));
+        assert_eq!(replies.len(), 1);
+        assert!(matches!(
+            start.try_recv(),
+            Ok(Err(TransportError::Cancelled))
+        ));
+        assert!(scheduler.pop().is_none());
+    }
+
+    #[test]
+    fn drops_duplicate_stats_requests() {
+        let mut scheduler = Scheduler::default();
+        let _first = push(&mut scheduler, Command::AskStats);
+        let _start = push(&mut scheduler, Command::StartBelt);
+        let _second = push(&mut scheduler, Command::AskStats);
+
+        let (command, replies) = scheduler.pop().unwrap();
+        assert_eq!(command, Command::AskStats);
+        assert_eq!(replies.len(), 2);
+        assert_eq!(scheduler.pop().unwrap().0, Command::StartBelt);
+        assert!(scheduler.pop().is_none());
+    }
+
+    #[test]
+    fn skips_cancelled_writes() {
+        let mut scheduler = Scheduler::default();
+        drop(push(&mut scheduler, Command::StartBelt));
+        let _stats = push(&mut scheduler, Command::AskStats);
+
+        assert_eq!(scheduler.pop().unwrap().0, Command::AskStats);
+        assert!(scheduler.pop().is_none());
+    }
+}
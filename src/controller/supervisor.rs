@@ -0,0 +1,104 @@
+//! Keeps the connection to the pad alive.
+//!
+//! While connected the supervisor polls the stats and publishes every decoded
+//! state. When the notifications end, a write fails or no status arrives for
+//! `STALE_TIMEOUT`, it reconnects with an exponential backoff and resumes
+//! polling. Every transition is published as a `ConnectionEvent`.
+
+use super::enums::ConnectionEvent;
+use super::protocol::Response;
+use super::{Control, Pad, Transport};
+
+use futures::stream::StreamExt;
+use log::{info, warn};
+use tokio::time::{Duration, Instant, MissedTickBehavior};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(750);
+const STALE_TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub(crate) async fn supervise<T: Transport>(pad: Pad<T>) {
+    pad.hub.publish_connection(ConnectionEvent::Connected);
+
+    loop {
+        let reason = watch(&pad).await;
+        if pad.is_closed() {
+            break;
+        }
+
+        warn!("Connection lost: {}", reason);
+        pad.hub
+            .publish_connection(ConnectionEvent::Disconnected { reason });
+
+        if !reconnect(&pad).await {
+            break;
+        }
+        pad.hub.publish_connection(ConnectionEvent::Connected);
+    }
+
+    info!("Connection closed");
+    pad.hub.publish_connection(ConnectionEvent::Closed);
+}
+
+/// Polls and publishes states until the connection looks dead, returns why.
+async fn watch<T: Transport>(pad: &Pad<T>) -> String {
+    let mut responses = match pad.responses().await {
+        Ok(responses) => responses,
+        Err(e) => return e.to_string(),
+    };
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_seen = Instant::now();
+
+    loop {
+        // Responses first, so that a slow write never makes queued ones look
+        // stale.
+        tokio::select! {
+            biased;
+            response = responses.next() => match response {
+                Some(Response::Status(state)) => {
+                    last_seen = Instant::now();
+                    pad.hub.publish(state);
+                }
+                Some(_) => {}
+                None => return "Notifications ended".to_string(),
+            },
+            _ = poll.tick() => {
+                if last_seen.elapsed() > STALE_TIMEOUT {
+                    return format!("No status for {:?}", last_seen.elapsed());
+                }
+                if let Err(e) = pad.ask_stats().await {
+                    return e.to_string();
+                }
+            }
+        }
+    }
+}
+
+/// Retries until connected again, `false` if the pad was closed meanwhile.
+async fn reconnect<T: Transport>(pad: &Pad<T>) -> bool {
+    let mut delay = MIN_BACKOFF;
+
+    for attempt in 1.. {
+        pad.hub.publish_connection(ConnectionEvent::Reconnecting {
+            attempt,
+            delay_ms: delay.as_millis() as u64,
+        });
+        tokio::time::sleep(delay).await;
+
+        match pad.request(Control::Connect).await {
+            Ok(()) => {
+                info!("Reconnected after {} attempts", attempt);
+                return true;
+            }
+            Err(_) if pad.is_closed() => return false,
+            Err(e) => {
+                warn!("Reconnect attempt {} failed: {}", attempt, e);
+                delay = (delay * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    false
+}
@@ -0,0 +1,80 @@
+//! The link `Pad` talks to the pad over.
+//!
+//! A transport only moves bytes: it writes whole command frames and yields the
+//! notified bytes as they arrive, possibly split or glued together. Framing,
+//! decoding, pacing and reconnect policy all stay above it in `Pad`. Errors are
+//! reported as `TransportError`, which does not tie the link to BLE.
+
+use super::error::TransportError;
+
+use async_trait::async_trait;
+use futures::stream::Stream;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Raw bytes notified by the pad.
+pub type Notifications = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+#[async_trait]
+pub trait Transport: Send + 'static {
+    /// Connects and subscribes to the notifications. Called again to
+    /// reconnect after the link was lost.
+    async fn connect(&mut self) -> Result<(), TransportError>;
+
+    async fn disconnect(&mut self) -> Result<(), TransportError>;
+
+    /// Writes one encoded command frame.
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+
+    /// Bytes notified from now on. The stream ends when the link is lost.
+    async fn notifications(&mut self) -> Result<Notifications, TransportError>;
+}
+
+#[async_trait]
+impl Transport for Box<dyn Transport> {
+    async fn connect(&mut self) -> Result<(), TransportError> {
+        (**self).connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        (**self).disconnect().await
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        (**self).write(frame).await
+    }
+
+    async fn notifications(&mut self) -> Result<Notifications, TransportError> {
+        (**self).notifications().await
+    }
+}
+
+/// Hands out `Notifications` streams and feeds them all, for transports that
+/// produce the notified bytes themselves.
+#[derive(Debug, Clone, Default)]
+pub struct Listeners {
+    senders: Arc<Mutex<Vec<mpsc::UnboundedSender<Vec<u8>>>>>,
+}
+
+impl Listeners {
+    pub fn stream(&self) -> Notifications {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.senders.lock().unwrap().push(tx);
+        Box::pin(futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|data| (data, rx))
+        }))
+    }
+
+    pub fn send(&self, data: &[u8]) {
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(data.to_vec()).is_ok());
+    }
+
+    /// Ends every stream handed out so far.
+    pub fn close(&self) {
+        self.senders.lock().unwrap().clear();
+    }
+}
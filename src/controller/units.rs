@@ -0,0 +1,144 @@
+//! Physical quantities reported by and sent to the pad.
+//!
+//! On the wire speed is in tenths of km/h, distance in tens of metres and time
+//! in seconds. These types keep that knowledge in one place and convert to
+//! whatever the user reads. They serialize in metric: km/h, metres and seconds.
+//! Speeds deserialize from km/h too.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+const KM_PER_MILE: f64 = 1.609_344;
+
+/// Belt speed, exact to the tenth of km/h the pad works with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Speed(u8);
+
+impl Speed {
+    pub const ZERO: Speed = Speed(0);
+
+    pub const fn from_tenths_kmh(tenths: u8) -> Self {
+        Speed(tenths)
+    }
+
+    /// Rounded to the closest tenth of km/h, saturating at 25.5 km/h.
+    pub fn from_kmh(kmh: f64) -> Self {
+        Speed((kmh * 10.0).round().clamp(0.0, u8::MAX as f64) as u8)
+    }
+
+    pub fn from_mph(mph: f64) -> Self {
+        Speed::from_kmh(mph * KM_PER_MILE)
+    }
+
+    pub fn tenths_kmh(self) -> u8 {
+        self.0
+    }
+
+    pub fn kmh(self) -> f64 {
+        self.0 as f64 / 10.0
+    }
+
+    pub fn mph(self) -> f64 {
+        self.kmh() / KM_PER_MILE
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} km/h", self.kmh())
+    }
+}
+
+impl Serialize for Speed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.kmh())
+    }
+}
+
+impl<'de> Deserialize<'de> for Speed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let kmh = f64::deserialize(deserializer)?;
+        if (0.0..=25.5).contains(&kmh) {
+            Ok(Speed::from_kmh(kmh))
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "Speed not allowed! {}",
+                kmh
+            )))
+        }
+    }
+}
+
+/// Distance walked, in metres.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Distance(u32);
+
+impl Distance {
+    pub const fn from_metres(metres: u32) -> Self {
+        Distance(metres)
+    }
+
+    pub fn metres(self) -> u32 {
+        self.0
+    }
+
+    pub fn km(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    pub fn miles(self) -> f64 {
+        self.km() / KM_PER_MILE
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} km", self.km())
+    }
+}
+
+impl Serialize for Distance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+/// Time walked, in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Elapsed(u32);
+
+impl Elapsed {
+    pub const fn from_secs(secs: u32) -> Self {
+        Elapsed(secs)
+    }
+
+    pub fn secs(self) -> u32 {
+        self.0
+    }
+
+    pub fn as_duration(self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.0 as u64)
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{:02}:{:02}",
+            self.0 / 3600,
+            self.0 / 60 % 60,
+            self.0 % 60
+        )
+    }
+}
+
+impl Serialize for Elapsed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
@@ -1,10 +1,88 @@
-trait Dao {
-    fn create(&self) -> Result<(), DaoError>;
-    fn read(&self) -> Result<(), DaoError>;
-    fn update(&self) -> Result<(), DaoError>;
-    fn delete(&self) -> Result<(), DaoError>;
+//! Persistence of the sessions, their samples and settings.
+
+use crate::controller::ramp::Ramps;
+use crate::session::Session;
+
+use async_trait::async_trait;
+use derive_more::{Display, Error as DError};
+use serde::Serialize;
+
+pub mod sqlite;
+pub use sqlite::SqliteDao;
+
+#[async_trait]
+pub trait Dao: Send + Sync + 'static {
+    /// Stores a finished session of `pad` with its samples, returns its id.
+    async fn create_session(&self, pad: &str, session: &Session) -> Result<i64, DaoError>;
+    /// Stores a session read from the history of `pad`, unless a session of the
+    /// pad with the same time, distance and steps is stored already, whether
+    /// tracked live or imported before. Returns the id if it is new.
+    async fn import_session(&self, pad: &str, session: &Session) -> Result<Option<i64>, DaoError>;
+    /// Every stored session, latest first, without the samples.
+    async fn read_sessions(&self) -> Result<Vec<StoredSession>, DaoError>;
+    /// One stored session with its samples.
+    async fn read_session(&self, id: i64) -> Result<Option<StoredSession>, DaoError>;
+    /// Whether there was such a session.
+    async fn delete_session(&self, id: i64) -> Result<bool, DaoError>;
+    async fn read_setting(&self, key: &str) -> Result<Option<String>, DaoError>;
+    async fn update_setting(&self, key: &str, value: &str) -> Result<(), DaoError>;
+}
+
+impl dyn Dao {
+    /// Ramps last set for `pad` through the API.
+    pub async fn read_ramps(&self, pad: &str) -> Result<Option<Ramps>, DaoError> {
+        match self.read_setting(&ramps_key(pad)).await? {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn update_ramps(&self, pad: &str, ramps: &Ramps) -> Result<(), DaoError> {
+        let json = serde_json::to_string(ramps)?;
+        self.update_setting(&ramps_key(pad), &json).await
+    }
+}
+
+fn ramps_key(pad: &str) -> String {
+    format!("pads.{}.ramps", pad)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoredSession {
+    pub id: i64,
+    pub pad: String,
+    #[serde(flatten)]
+    pub session: Session,
+    /// Read from the pad history, whose records have no dates: the start and
+    /// end are only when the daemon fetched them.
+    pub imported: bool,
+}
+
+#[derive(Display, Debug, DError)]
+pub struct DaoError {
+    pub details: String,
+}
+
+impl From<rusqlite::Error> for DaoError {
+    fn from(e: rusqlite::Error) -> Self {
+        DaoError {
+            details: e.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for DaoError {
+    fn from(e: serde_json::Error) -> Self {
+        DaoError {
+            details: e.to_string(),
+        }
+    }
 }
 
-struct DaoError {
-    details: String,
+impl From<tokio::task::JoinError> for DaoError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        DaoError {
+            details: e.to_string(),
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! `Dao` on a SQLite database.
+//!
+//! rusqlite blocks, so every query runs on the blocking thread pool. The schema
+//! is versioned with `PRAGMA user_version`: the migrations past the version of
+//! the database are applied when it is opened.
+
+use super::{Dao, DaoError, StoredSession};
+use crate::controller::units::{Distance, Elapsed, Speed};
+use crate::session::{Sample, Session};
+
+use async_trait::async_trait;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// One entry per schema version, never edit the ones already released.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        pad TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        distance INTEGER NOT NULL,
+        steps INTEGER NOT NULL,
+        average_speed INTEGER NOT NULL,
+        max_speed INTEGER NOT NULL
+    );
+    CREATE INDEX sessions_started_at ON sessions (started_at);
+
+    CREATE TABLE samples (
+        session INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        offset_secs INTEGER NOT NULL,
+        speed INTEGER NOT NULL,
+        distance INTEGER NOT NULL,
+        steps INTEGER NOT NULL,
+        PRIMARY KEY (session, offset_secs)
+    ) WITHOUT ROWID;
+
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+",
+    "
+    ALTER TABLE sessions ADD COLUMN calories REAL;
+    ALTER TABLE samples ADD COLUMN calories REAL;
+",
+    "
+    ALTER TABLE sessions ADD COLUMN history_index INTEGER;
+    CREATE UNIQUE INDEX sessions_history ON sessions (pad, history_index, time)
+        WHERE history_index IS NOT NULL;
+",
+    "
+    ALTER TABLE sessions ADD COLUMN imported INTEGER NOT NULL DEFAULT 0;
+    UPDATE sessions SET imported = 1 WHERE history_index IS NOT NULL;
+    DROP INDEX sessions_history;
+    CREATE INDEX sessions_totals ON sessions (pad, time, distance, steps);
+",
+];
+
+const SESSION_COLUMNS: &str = "id, pad, started_at, ended_at, time, distance, steps, \
+     average_speed, max_speed, calories, imported";
+
+#[derive(Debug, Clone)]
+pub struct SqliteDao {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDao {
+    /// Opens the database, creating it if needed, and migrates it.
+    pub fn open(path: &Path) -> Result<Self, DaoError> {
+        SqliteDao::new(Connection::open(path)?)
+    }
+
+    /// A database that lives as long as the returned value.
+    pub fn in_memory() -> Result<Self, DaoError> {
+        SqliteDao::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut connection: Connection) -> Result<Self, DaoError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<R, F>(&self, query: F) -> Result<R, DaoError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<R> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let result = tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            query(&mut connection)
+        })
+        .await?;
+
+        Ok(result?)
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), DaoError> {
+    let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let version = version as usize;
+    if version > MIGRATIONS.len() {
+        return Err(DaoError {
+            details: format!(
+                "Database schema {} is newer than the supported {}",
+                version,
+                MIGRATIONS.len()
+            ),
+        });
+    }
+
+    for (number, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        info!("Migrating database to schema {}", number + 1);
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", (number + 1) as u32)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+fn session(row: &Row) -> rusqlite::Result<StoredSession> {
+    Ok(StoredSession {
+        id: row.get(0)?,
+        pad: row.get(1)?,
+        session: Session {
+            started_at: row.get(2)?,
+            ended_at: row.get(3)?,
+            time: Elapsed::from_secs(row.get(4)?),
+            distance: Distance::from_metres(row.get(5)?),
+            steps: row.get::<_, i64>(6)? as usize,
+            average_speed: Speed::from_tenths_kmh(row.get(7)?),
+            max_speed: Speed::from_tenths_kmh(row.get(8)?),
+            calories: row.get(9)?,
+            samples: vec![],
+        },
+        imported: row.get(10)?,
+    })
+}
+
+fn sample(row: &Row) -> rusqlite::Result<Sample> {
+    Ok(Sample {
+        offset: row.get(0)?,
+        speed: Speed::from_tenths_kmh(row.get(1)?),
+        distance: Distance::from_metres(row.get(2)?),
+        steps: row.get::<_, i64>(3)? as usize,
+        calories: row.get(4)?,
+    })
+}
+
+/// Inserts the session with its samples, returns its id.
+fn insert(
+    transaction: &Transaction,
+    pad: &str,
+    imported: bool,
+    session: &Session,
+) -> rusqlite::Result<i64> {
+    transaction.execute(
+        "INSERT INTO sessions (pad, started_at, ended_at, time, distance, steps,
+            average_speed, max_speed, calories, imported)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            pad,
+            session.started_at,
+            session.ended_at,
+            session.time.secs(),
+            session.distance.metres(),
+            session.steps as i64,
+            session.average_speed.tenths_kmh(),
+            session.max_speed.tenths_kmh(),
+            session.calories,
+            imported,
+        ],
+    )?;
+    let id = transaction.last_insert_rowid();
+
+    let mut insert = transaction.prepare(
+        "INSERT INTO samples (session, offset_secs, speed, distance, steps, calories)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for sample in &session.samples {
+        insert.execute(params![
+            id,
+            sample.offset,
+            sample.speed.tenths_kmh(),
+            sample.distance.metres(),
+            sample.steps as i64,
+            sample.calories,
+        ])?;
+    }
+
+    Ok(id)
+}
+
+#[async_trait]
+impl Dao for SqliteDao {
+    async fn create_session(&self, pad: &str, session: &Session) -> Result<i64, DaoError> {
+        let pad = pad.to_string();
+        let session = session.clone();
+        self.run(move |connection| {
+            let transaction = connection.transaction()?;
+            let id = insert(&transaction, &pad, false, &session)?;
+            transaction.commit()?;
+            Ok(id)
+        })
+        .await
+    }
+
+    async fn import_session(&self, pad: &str, session: &Session) -> Result<Option<i64>, DaoError> {
+        let pad = pad.to_string();
+        let session = session.clone();
+        self.run(move |connection| {
+            let transaction = connection.transaction()?;
+            let stored: bool = transaction.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sessions
+                    WHERE pad = ?1 AND time = ?2 AND distance = ?3 AND steps = ?4)",
+                params![
+                    pad,
+                    session.time.secs(),
+                    session.distance.metres(),
+                    session.steps as i64,
+                ],
+                |row| row.get(0),
+            )?;
+            if stored {
+                return Ok(None);
+            }
+            let id = insert(&transaction, &pad, true, &session)?;
+            transaction.commit()?;
+            Ok(Some(id))
+        })
+        .await
+    }
+
+    async fn read_sessions(&self) -> Result<Vec<StoredSession>, DaoError> {
+        self.run(|connection| {
+            connection
+                .prepare(&format!(
+                    "SELECT {} FROM sessions ORDER BY started_at DESC, id DESC",
+                    SESSION_COLUMNS
+                ))?
+                .query_map([], session)?
+                .collect()
+        })
+        .await
+    }
+
+    async fn read_session(&self, id: i64) -> Result<Option<StoredSession>, DaoError> {
+        self.run(move |connection| {
+            let stored = connection
+                .query_row(
+                    &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+                    [id],
+                    session,
+                )
+                .optional()?;
+
+            match stored {
+                Some(mut stored) => {
+                    stored.session.samples = connection
+                        .prepare(
+                            "SELECT offset_secs, speed, distance, steps, calories FROM samples
+                             WHERE session = ?1 ORDER BY offset_secs",
+                        )?
+                        .query_map([id], sample)?
+                        .collect::<rusqlite::Result<_>>()?;
+                    Ok(Some(stored))
+                }
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn delete_session(&self, id: i64) -> Result<bool, DaoError> {
+        self.run(move |connection| {
+            Ok(connection.execute("DELETE FROM sessions WHERE id = ?1", [id])? > 0)
+        })
+        .await
+    }
+
+    async fn read_setting(&self, key: &str) -> Result<Option<String>, DaoError> {
+        let key = key.to_string();
+        self.run(move |connection| {
+            connection
+                .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                    row.get(0)
+                })
+                .optional()
+        })
+        .await
+    }
+
+    async fn update_setting(&self, key: &str, value: &str) -> Result<(), DaoError> {
+        let key = key.to_string();
+        let value = value.to_string();
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                [key, value],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,150 @@
+//! Finding pads around us.
+//!
+//! A peripheral is a candidate when it matches every criterion that is set in
+//! `DiscoveryOptions`: a case-insensitive name pattern, the MAC address or the
+//! advertised FE00 service. Candidates are returned strongest signal first.
+
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{BDAddr, Central, Manager, Peripheral, ScanFilter};
+use derive_more::{Display, Error as DError};
+use log::{info, warn};
+use std::str::FromStr;
+use std::time::Duration;
+
+const FE00: u16 = 0xfe00;
+
+#[derive(Display, Debug, DError)]
+pub struct DiscoveryError {
+    pub details: String,
+}
+
+impl From<btleplug::Error> for DiscoveryError {
+    fn from(e: btleplug::Error) -> Self {
+        DiscoveryError {
+            details: e.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for AdapterSelector {
+    type Err = std::convert::Infallible;
+
+    /// A number selects the adapter by index, anything else by name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => AdapterSelector::Index(index),
+            Err(_) => AdapterSelector::Name(s.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    pub adapter: AdapterSelector,
+    pub name: Option<String>,
+    pub address: Option<BDAddr>,
+    pub service: bool,
+    pub timeout: Duration,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            adapter: AdapterSelector::Index(0),
+            name: Some("walkingpad".to_string()),
+            address: None,
+            service: false,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate<P: Peripheral> {
+    pub peripheral: P,
+    pub name: Option<String>,
+    pub address: BDAddr,
+    pub rssi: Option<i16>,
+}
+
+/// Picks the adapter by index or by a case-insensitive part of its info string.
+pub async fn select_adapter<M: Manager>(
+    manager: &M,
+    selector: &AdapterSelector,
+) -> Result<M::Adapter, DiscoveryError> {
+    let adapters = manager.adapters().await?;
+
+    match selector {
+        AdapterSelector::Index(index) => adapters.get(*index).cloned().ok_or(DiscoveryError {
+            details: format!("No Bluetooth adapter with index {}", index),
+        }),
+        AdapterSelector::Name(name) => {
+            for adapter in adapters {
+                let info = adapter.adapter_info().await?;
+                if info.to_lowercase().contains(&name.to_lowercase()) {
+                    return Ok(adapter);
+                }
+            }
+
+            Err(DiscoveryError {
+                details: format!("No Bluetooth adapter named {}", name),
+            })
+        }
+    }
+}
+
+/// Scans for `options.timeout` and returns every matching peripheral.
+pub async fn discover<A: Central>(
+    adapter: &A,
+    options: &DiscoveryOptions,
+) -> Result<Vec<Candidate<A::Peripheral>>, DiscoveryError> {
+    info!("Starting scan on {}...", adapter.adapter_info().await?);
+    adapter.start_scan(ScanFilter::default()).await?;
+    tokio::time::sleep(options.timeout).await;
+    adapter.stop_scan().await?;
+
+    let mut candidates = vec![];
+    for peripheral in adapter.peripherals().await? {
+        let properties = match peripheral.properties().await {
+            Ok(Some(properties)) => properties,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Reading properties failed: {}", e);
+                continue;
+            }
+        };
+
+        let name_matches = match (&options.name, &properties.local_name) {
+            (Some(pattern), Some(name)) => name.to_lowercase().contains(&pattern.to_lowercase()),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let address_matches = options
+            .address
+            .is_none_or(|address| address == properties.address);
+        let service_matches =
+            !options.service || properties.services.contains(&uuid_from_u16(FE00));
+
+        if name_matches && address_matches && service_matches {
+            info!(
+                "Found {} ({:?}), RSSI {:?}",
+                properties.address, properties.local_name, properties.rssi
+            );
+            candidates.push(Candidate {
+                peripheral,
+                name: properties.local_name,
+                address: properties.address,
+                rssi: properties.rssi,
+            });
+        }
+    }
+
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.rssi));
+    Ok(candidates)
+}
@@ -0,0 +1,260 @@
+//! Garmin FIT activity file, a walk on a treadmill.
+//!
+//! Only the messages an activity needs are written: file id, timer start and
+//! stop events, a record per sample, one lap, the session and the activity.
+//! Every kind of message gets its own local message type, defined before its
+//! first use.
+
+use super::metres_per_second;
+use crate::controller::units::Speed;
+use crate::session::Session;
+
+use chrono::{DateTime, Duration, Utc};
+
+const PROTOCOL_VERSION: u8 = 0x20;
+const PROFILE_VERSION: u16 = 2132;
+/// Unix time of 1989-12-31T00:00:00Z, where FIT time starts.
+const FIT_EPOCH: i64 = 631_065_600;
+const HEADER_LEN: u8 = 14;
+
+const FILE_ID: u16 = 0;
+const SESSION: u16 = 18;
+const LAP: u16 = 19;
+const RECORD: u16 = 20;
+const EVENT: u16 = 21;
+const ACTIVITY: u16 = 34;
+
+const TIMESTAMP: u8 = 253;
+const FILE_TYPE_ACTIVITY: u8 = 4;
+const ACTIVITY_TYPE_MANUAL: u8 = 0;
+const SPORT_WALKING: u8 = 11;
+const SUB_SPORT_TREADMILL: u8 = 1;
+const MANUFACTURER_DEVELOPMENT: u16 = 255;
+const EVENT_TIMER: u8 = 0;
+const EVENT_LAP: u8 = 9;
+const EVENT_SESSION: u8 = 8;
+const EVENT_ACTIVITY: u8 = 26;
+const EVENT_TYPE_START: u8 = 0;
+const EVENT_TYPE_STOP: u8 = 1;
+const EVENT_TYPE_STOP_ALL: u8 = 4;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xcc01, 0xd801, 0x1400, 0xf001, 0x3c00, 0x2800, 0xe401, 0xa001, 0x6c00, 0x7800, 0xb401,
+    0x5000, 0x9c01, 0x8801, 0x4400,
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Enum(u8),
+    U16(u16),
+    U32(u32),
+    /// Zero is invalid.
+    U32z(u32),
+}
+
+impl Field {
+    fn base_type(self) -> u8 {
+        match self {
+            Field::Enum(_) => 0x00,
+            Field::U16(_) => 0x84,
+            Field::U32(_) => 0x86,
+            Field::U32z(_) => 0x8c,
+        }
+    }
+
+    fn write(self, data: &mut Vec<u8>) {
+        match self {
+            Field::Enum(value) => data.push(value),
+            Field::U16(value) => data.extend(value.to_le_bytes()),
+            Field::U32(value) | Field::U32z(value) => data.extend(value.to_le_bytes()),
+        }
+    }
+
+    fn size(self) -> u8 {
+        match self {
+            Field::Enum(_) => 1,
+            Field::U16(_) => 2,
+            Field::U32(_) | Field::U32z(_) => 4,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    data: Vec<u8>,
+    /// Global message number of every local message type defined so far.
+    defined: Vec<u16>,
+}
+
+impl Encoder {
+    fn message(&mut self, global: u16, fields: &[(u8, Field)]) {
+        let local = match self.defined.iter().position(|&defined| defined == global) {
+            Some(local) => local as u8,
+            None => {
+                let local = self.defined.len() as u8;
+                self.defined.push(global);
+                self.data.extend([0x40 | local, 0, 0]);
+                self.data.extend(global.to_le_bytes());
+                self.data.push(fields.len() as u8);
+                for (number, field) in fields {
+                    self.data.extend([*number, field.size(), field.base_type()]);
+                }
+                local
+            }
+        };
+
+        self.data.push(local);
+        for (_, field) in fields {
+            field.write(&mut self.data);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut file = vec![HEADER_LEN, PROTOCOL_VERSION];
+        file.extend(PROFILE_VERSION.to_le_bytes());
+        file.extend((self.data.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(crc(&file).to_le_bytes());
+        file.extend(self.data);
+        file.extend(crc(&file).to_le_bytes());
+        file
+    }
+}
+
+fn crc(data: &[u8]) -> u16 {
+    data.iter().fold(0, |mut crc, &byte| {
+        for nibble in [byte & 0xf, byte >> 4] {
+            let tmp = CRC_TABLE[(crc & 0xf) as usize];
+            crc = (crc >> 4) & 0x0fff;
+            crc = crc ^ tmp ^ CRC_TABLE[nibble as usize];
+        }
+        crc
+    })
+}
+
+fn fit_time(at: DateTime<Utc>) -> Field {
+    Field::U32((at.timestamp() - FIT_EPOCH).max(0) as u32)
+}
+
+/// Millimetres per second.
+fn fit_speed(speed: Speed) -> Field {
+    Field::U16((metres_per_second(speed) * 1000.0).round() as u16)
+}
+
+/// Centimetres.
+fn fit_distance(metres: u32) -> Field {
+    Field::U32(metres * 100)
+}
+
+/// Milliseconds.
+fn fit_duration(secs: i64) -> Field {
+    Field::U32((secs.max(0) * 1000) as u32)
+}
+
+pub fn encode(session: &Session) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    let start = fit_time(session.started_at);
+    let end = fit_time(session.ended_at);
+    let elapsed = fit_duration((session.ended_at - session.started_at).num_seconds());
+    let timer = fit_duration(session.time.secs() as i64);
+    let distance = fit_distance(session.distance.metres());
+    // Walking cycles are strides, two steps each.
+    let strides = Field::U32(session.steps as u32 / 2);
+    // Unknown without a profile, which FIT marks with the largest value.
+    let calories = Field::U16(
+        session
+            .calories
+            .map_or(u16::MAX, |kcal| kcal.round() as u16),
+    );
+
+    encoder.message(
+        FILE_ID,
+        &[
+            (0, Field::Enum(FILE_TYPE_ACTIVITY)),
+            (1, Field::U16(MANUFACTURER_DEVELOPMENT)),
+            (2, Field::U16(0)),
+            (3, Field::U32z(1)),
+            (4, start),
+        ],
+    );
+    encoder.message(
+        EVENT,
+        &[
+            (TIMESTAMP, start),
+            (0, Field::Enum(EVENT_TIMER)),
+            (1, Field::Enum(EVENT_TYPE_START)),
+        ],
+    );
+
+    for sample in &session.samples {
+        let at = session.started_at + Duration::seconds(sample.offset as i64);
+        encoder.message(
+            RECORD,
+            &[
+                (TIMESTAMP, fit_time(at)),
+                (5, fit_distance(sample.distance.metres())),
+                (6, fit_speed(sample.speed)),
+            ],
+        );
+    }
+
+    encoder.message(
+        EVENT,
+        &[
+            (TIMESTAMP, end),
+            (0, Field::Enum(EVENT_TIMER)),
+            (1, Field::Enum(EVENT_TYPE_STOP_ALL)),
+        ],
+    );
+    encoder.message(
+        LAP,
+        &[
+            (TIMESTAMP, end),
+            (0, Field::Enum(EVENT_LAP)),
+            (1, Field::Enum(EVENT_TYPE_STOP)),
+            (2, start),
+            (7, elapsed),
+            (8, timer),
+            (9, distance),
+            (10, strides),
+            (11, calories),
+            (13, fit_speed(session.average_speed)),
+            (14, fit_speed(session.max_speed)),
+            (25, Field::Enum(SPORT_WALKING)),
+            (39, Field::Enum(SUB_SPORT_TREADMILL)),
+        ],
+    );
+    encoder.message(
+        SESSION,
+        &[
+            (TIMESTAMP, end),
+            (0, Field::Enum(EVENT_SESSION)),
+            (1, Field::Enum(EVENT_TYPE_STOP)),
+            (2, start),
+            (5, Field::Enum(SPORT_WALKING)),
+            (6, Field::Enum(SUB_SPORT_TREADMILL)),
+            (7, elapsed),
+            (8, timer),
+            (9, distance),
+            (10, strides),
+            (11, calories),
+            (14, fit_speed(session.average_speed)),
+            (15, fit_speed(session.max_speed)),
+            (25, Field::U16(0)),
+            (26, Field::U16(1)),
+        ],
+    );
+    encoder.message(
+        ACTIVITY,
+        &[
+            (TIMESTAMP, end),
+            (0, timer),
+            (1, Field::U16(1)),
+            (2, Field::Enum(ACTIVITY_TYPE_MANUAL)),
+            (3, Field::Enum(EVENT_ACTIVITY)),
+            (4, Field::Enum(EVENT_TYPE_STOP)),
+        ],
+    );
+
+    encoder.finish()
+}
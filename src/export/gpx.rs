@@ -0,0 +1,65 @@
+//! GPX 1.1 track along a virtual route.
+//!
+//! The pad counts distance in steps of 10 m, which would make the positions
+//! jump. They follow the speed of the samples instead, scaled so that the walk
+//! ends at the distance the pad counted.
+
+use super::{metres_per_second, timestamp, Route};
+use crate::session::Session;
+
+use chrono::Duration;
+use std::fmt::Write;
+
+pub fn encode(session: &Session, route: &Route) -> String {
+    let mut gpx = String::new();
+
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str(concat!(
+        "<gpx version=\"1.1\" creator=\"walkingpad\"",
+        " xmlns=\"http://www.topografix.com/GPX/1/1\"",
+        " xmlns:gpxtpx=\"http://www.garmin.com/xmlschemas/TrackPointExtension/v2\">\n",
+    ));
+    let _ = writeln!(
+        gpx,
+        "  <metadata><time>{}</time></metadata>",
+        timestamp(session.started_at)
+    );
+    gpx.push_str("  <trk>\n    <name>Treadmill walk</name>\n    <type>walking</type>\n");
+    gpx.push_str("    <trkseg>\n");
+
+    for (sample, distance) in session.samples.iter().zip(distances(session)) {
+        let (lat, lon) = route.position(distance);
+        let at = session.started_at + Duration::seconds(sample.offset as i64);
+        let _ = writeln!(gpx, "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">", lat, lon);
+        let _ = writeln!(gpx, "        <time>{}</time>", timestamp(at));
+        let _ = writeln!(
+            gpx,
+            "        <extensions><gpxtpx:TrackPointExtension><gpxtpx:speed>{:.3}</gpxtpx:speed></gpxtpx:TrackPointExtension></extensions>",
+            metres_per_second(sample.speed)
+        );
+        gpx.push_str("      </trkpt>\n");
+    }
+
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    gpx
+}
+
+/// Metres walked at every sample.
+fn distances(session: &Session) -> Vec<f64> {
+    let mut walked = 0.0;
+    let mut distances: Vec<f64> = session.samples.iter().take(1).map(|_| 0.0).collect();
+    for pair in session.samples.windows(2) {
+        let seconds = (pair[1].offset - pair[0].offset) as f64;
+        walked += metres_per_second(pair[0].speed) * seconds;
+        distances.push(walked);
+    }
+
+    let counted = session.distance.metres() as f64;
+    if walked > 0.0 && counted > 0.0 {
+        for distance in &mut distances {
+            *distance *= counted / walked;
+        }
+    }
+
+    distances
+}
@@ -0,0 +1,95 @@
+//! Sessions as activity files for fitness platforms.
+//!
+//! Every format describes an indoor walk on a treadmill: the timestamps come
+//! from the start of the session and the offsets of its samples, distance and
+//! speed from the counters of the pad. TCX and FIT also carry the calories.
+//! GPX needs positions, so the walk follows a virtual `Route`.
+
+use crate::controller::units::Speed;
+use crate::session::Session;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use derive_more::{Display, Error as DError};
+use std::str::FromStr;
+
+mod fit;
+mod gpx;
+pub mod route;
+mod tcx;
+pub use route::Route;
+
+#[derive(Display, Debug, DError)]
+pub struct ExportError {
+    pub details: String,
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError {
+            details: e.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Garmin Training Center.
+    Tcx,
+    Gpx,
+    /// Garmin FIT activity.
+    Fit,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Tcx => "tcx",
+            Format::Gpx => "gpx",
+            Format::Fit => "fit",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Tcx => "application/vnd.garmin.tcx+xml",
+            Format::Gpx => "application/gpx+xml",
+            Format::Fit => "application/vnd.ant.fit",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tcx" => Ok(Format::Tcx),
+            "gpx" => Ok(Format::Gpx),
+            "fit" => Ok(Format::Fit),
+            _ => Err(format!("Unknown format {}, expected tcx, gpx or fit", s)),
+        }
+    }
+}
+
+/// The session in `format`. Only GPX uses the route, and follows
+/// `Route::default()` without one.
+pub fn export(session: &Session, format: Format, route: Option<&Route>) -> Vec<u8> {
+    match format {
+        Format::Tcx => tcx::encode(session).into_bytes(),
+        Format::Gpx => match route {
+            Some(route) => gpx::encode(session, route),
+            None => gpx::encode(session, &Route::default()),
+        }
+        .into_bytes(),
+        Format::Fit => fit::encode(session),
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Metres per second.
+fn metres_per_second(speed: Speed) -> f64 {
+    speed.kmh() / 3.6
+}
@@ -0,0 +1,124 @@
+//! Virtual routes to give a walk on the belt positions.
+
+use super::ExportError;
+
+use std::path::Path;
+
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// A path of (latitude, longitude) points in degrees. A walk longer than the
+/// route starts over at its first point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    points: Vec<(f64, f64)>,
+    /// Metres from the first point to every point.
+    distances: Vec<f64>,
+}
+
+impl Default for Route {
+    /// Due north from 0°N 0°E, for about 111 km.
+    fn default() -> Self {
+        Route::new(vec![(0.0, 0.0), (1.0, 0.0)]).unwrap()
+    }
+}
+
+impl Route {
+    /// Needs two points at least.
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self, ExportError> {
+        if points.len() < 2 {
+            return Err(ExportError {
+                details: format!("A route needs 2 points at least, got {}", points.len()),
+            });
+        }
+
+        let mut distances = vec![0.0];
+        for pair in points.windows(2) {
+            let last = distances[distances.len() - 1];
+            distances.push(last + haversine(pair[0], pair[1]));
+        }
+
+        Ok(Self { points, distances })
+    }
+
+    /// The track or route points of a GPX file.
+    pub fn read(path: &Path) -> Result<Self, ExportError> {
+        Route::parse_gpx(&std::fs::read_to_string(path)?)
+    }
+
+    /// Picks the `lat` and `lon` attributes of every `trkpt` and `rtept`, this
+    /// is no general XML parser.
+    pub fn parse_gpx(gpx: &str) -> Result<Self, ExportError> {
+        let mut points = vec![];
+        for (start, _) in gpx.match_indices('<') {
+            let tag = &gpx[start + 1..];
+            if !(tag.starts_with("trkpt") || tag.starts_with("rtept")) {
+                continue;
+            }
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            match (attribute(tag, "lat"), attribute(tag, "lon")) {
+                (Some(lat), Some(lon)) => points.push((lat, lon)),
+                _ => {
+                    return Err(ExportError {
+                        details: format!("Point without a position: <{}>", tag),
+                    })
+                }
+            }
+        }
+
+        Route::new(points)
+    }
+
+    /// Metres from the first to the last point.
+    pub fn length(&self) -> f64 {
+        self.distances[self.distances.len() - 1]
+    }
+
+    /// Where one is after walking `distance` metres along the route.
+    pub fn position(&self, distance: f64) -> (f64, f64) {
+        let length = self.length();
+        let distance = if length > 0.0 {
+            distance.rem_euclid(length)
+        } else {
+            0.0
+        };
+
+        let next = self
+            .distances
+            .iter()
+            .position(|&d| d > distance)
+            .unwrap_or(self.points.len() - 1)
+            .max(1);
+        let (from, to) = (self.points[next - 1], self.points[next]);
+        let leg = self.distances[next] - self.distances[next - 1];
+        let ratio = if leg > 0.0 {
+            (distance - self.distances[next - 1]) / leg
+        } else {
+            0.0
+        };
+
+        (
+            from.0 + (to.0 - from.0) * ratio,
+            from.1 + (to.1 - from.1) * ratio,
+        )
+    }
+}
+
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+    let pattern = format!("{}=", name);
+    let (at, _) = tag
+        .match_indices(&pattern)
+        .find(|(at, _)| tag[..*at].ends_with(char::is_whitespace))?;
+    let value = &tag[at + pattern.len()..];
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    value[..value.find(quote)?].trim().parse().ok()
+}
+
+/// Metres between two points.
+fn haversine(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (to.1 - from.1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
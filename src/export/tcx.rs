@@ -0,0 +1,80 @@
+//! Garmin Training Center XML, one lap with a trackpoint per sample.
+//!
+//! TCX knows no walking, so the activity is `Other`. Speed and steps go into
+//! the ActivityExtension.
+
+use super::{metres_per_second, timestamp};
+use crate::session::Session;
+
+use chrono::Duration;
+use std::fmt::Write;
+
+pub fn encode(session: &Session) -> String {
+    let mut tcx = String::new();
+    let start = timestamp(session.started_at);
+
+    tcx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    tcx.push_str(concat!(
+        "<TrainingCenterDatabase",
+        " xmlns=\"http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2\"",
+        " xmlns:ns3=\"http://www.garmin.com/xmlschemas/ActivityExtension/v2\">\n",
+    ));
+    tcx.push_str("  <Activities>\n    <Activity Sport=\"Other\">\n");
+    let _ = writeln!(tcx, "      <Id>{}</Id>", start);
+    let _ = writeln!(tcx, "      <Lap StartTime=\"{}\">", start);
+    let _ = writeln!(
+        tcx,
+        "        <TotalTimeSeconds>{}</TotalTimeSeconds>",
+        session.time.secs()
+    );
+    let _ = writeln!(
+        tcx,
+        "        <DistanceMeters>{}</DistanceMeters>",
+        session.distance.metres()
+    );
+    let _ = writeln!(
+        tcx,
+        "        <MaximumSpeed>{:.3}</MaximumSpeed>",
+        metres_per_second(session.max_speed)
+    );
+    let _ = writeln!(
+        tcx,
+        "        <Calories>{}</Calories>",
+        session.calories.unwrap_or(0.0).round()
+    );
+    tcx.push_str("        <Intensity>Active</Intensity>\n");
+    tcx.push_str("        <TriggerMethod>Manual</TriggerMethod>\n");
+
+    tcx.push_str("        <Track>\n");
+    for sample in &session.samples {
+        let at = session.started_at + Duration::seconds(sample.offset as i64);
+        tcx.push_str("          <Trackpoint>\n");
+        let _ = writeln!(tcx, "            <Time>{}</Time>", timestamp(at));
+        let _ = writeln!(
+            tcx,
+            "            <DistanceMeters>{}</DistanceMeters>",
+            sample.distance.metres()
+        );
+        let _ = writeln!(
+            tcx,
+            "            <Extensions><ns3:TPX><ns3:Speed>{:.3}</ns3:Speed></ns3:TPX></Extensions>",
+            metres_per_second(sample.speed)
+        );
+        tcx.push_str("          </Trackpoint>\n");
+    }
+    tcx.push_str("        </Track>\n");
+
+    tcx.push_str("        <Extensions>\n          <ns3:LX>\n");
+    let _ = writeln!(
+        tcx,
+        "            <ns3:AvgSpeed>{:.3}</ns3:AvgSpeed>",
+        metres_per_second(session.average_speed)
+    );
+    let _ = writeln!(tcx, "            <ns3:Steps>{}</ns3:Steps>", session.steps);
+    tcx.push_str("          </ns3:LX>\n        </Extensions>\n");
+    tcx.push_str("      </Lap>\n");
+    tcx.push_str("      <Notes>Treadmill walk</Notes>\n");
+    tcx.push_str("    </Activity>\n  </Activities>\n</TrainingCenterDatabase>\n");
+
+    tcx
+}
@@ -1,49 +1,252 @@
-use crate::controller::Pad;
+use crate::controller::registry::Registry;
+use crate::controller::{Pad, Transport};
+use crate::dao::Dao;
+use crate::export::{Format, Route};
+use crate::profile::Profile;
+use crate::program::Programs;
+use crate::safety::{Leases, WalkOffs};
 use warp::{hyper::StatusCode, Filter, Rejection, Reply};
 
 use crate::http::handlers;
 
-use std::{collections::HashMap, convert::Infallible, error::Error};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
 
-/// The 4 TODOs filters combined.
-pub fn walkingpad<T: btleplug::api::Peripheral>(
-    pad: Pad<T>,
+/// What the endpoints need besides the pads.
+#[derive(Clone)]
+pub struct Context {
+    pub dao: Arc<dyn Dao>,
+    /// Route of the GPX downloads.
+    pub route: Option<Arc<Route>>,
+    /// The live state only has calories with it.
+    pub profile: Option<Profile>,
+    pub programs: Programs,
+    /// Pads without a lease do not need heartbeats.
+    pub leases: Leases,
+    pub walk_offs: WalkOffs,
+}
+
+/// All the filters combined.
+pub fn walkingpad<T: Transport>(
+    registry: Registry<T>,
+    context: Context,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
-    start_belt(pad.clone())
-        .or(stop_belt(pad.clone()))
-        .or(change_speed(pad.clone()))
+    pads(registry.clone())
+        .or(start_belt(registry.clone(), context.leases.clone()))
+        .or(stop_belt(registry.clone()))
+        .or(change_speed(registry.clone(), context.leases.clone()))
+        .or(heartbeat(registry.clone(), context.leases.clone()))
+        .or(lease(registry.clone(), context.leases.clone()))
+        .or(walk_off(registry.clone(), context.walk_offs))
+        .or(state(registry.clone(), context.profile))
+        .or(connection(registry.clone()))
+        .or(ramps(registry.clone()))
+        .or(set_ramps(registry.clone(), context.dao.clone()))
+        .or(start_program(registry.clone(), context.programs.clone()))
+        .or(program(registry.clone(), context.programs.clone()))
+        .or(control_program(registry, context.programs))
+        .or(sessions(context.dao.clone()))
+        .or(session(context.dao.clone()))
+        .or(export(context.dao, context.route))
         .recover(handle_rejection)
 }
 
-/// POST /!start_belt
-pub fn start_belt<T: btleplug::api::Peripheral>(
-    pad: Pad<T>,
+/// GET /pads
+pub fn pads<T: Transport>(
+    registry: Registry<T>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("pads")
+        .and(warp::get())
+        .and(warp::any().map(move || registry.clone()))
+        .and_then(handlers::pads)
+}
+
+/// POST /pads/:id/!start_belt, a heartbeat too
+pub fn start_belt<T: Transport>(
+    registry: Registry<T>,
+    leases: Leases,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("!start_belt")
+    with_id_and_pad(registry)
+        .and(warp::path!("!start_belt"))
         .and(warp::post())
-        .and(with_pad(pad))
+        .map(renewing(leases))
         .and_then(handlers::start_belt)
 }
 
-/// POST /!stop_belt
-pub fn stop_belt<T: btleplug::api::Peripheral>(
-    pad: Pad<T>,
+/// POST /pads/:id/!stop_belt
+pub fn stop_belt<T: Transport>(
+    registry: Registry<T>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("!stop_belt")
+    with_pad(registry)
+        .and(warp::path!("!stop_belt"))
         .and(warp::post())
-        .and(with_pad(pad))
         .and_then(handlers::stop_belt)
 }
 
-pub fn change_speed<T: btleplug::api::Peripheral>(
-    pad: Pad<T>,
+/// /pads/:id/!change_speed?kmh=:kmh, mph=:mph or speed=:tenths, a heartbeat too
+pub fn change_speed<T: Transport>(
+    registry: Registry<T>,
+    leases: Leases,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("!change_speed")
+    with_id_and_pad(registry)
+        .and(warp::path!("!change_speed"))
+        .map(renewing(leases))
         .and(warp::query::<HashMap<String, String>>())
-        .and(with_pad(pad))
         .and_then(handlers::change_speed)
 }
 
+/// GET /pads/:id/state?units=:units
+pub fn state<T: Transport>(
+    registry: Registry<T>,
+    profile: Option<Profile>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_pad(registry)
+        .and(warp::path!("state"))
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::any().map(move || profile.clone()))
+        .and_then(handlers::state)
+}
+
+/// GET /pads/:id/connection
+pub fn connection<T: Transport>(
+    registry: Registry<T>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_pad(registry)
+        .and(warp::path!("connection"))
+        .and(warp::get())
+        .and_then(handlers::connection)
+}
+
+/// GET /pads/:id/ramps
+pub fn ramps<T: Transport>(
+    registry: Registry<T>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_pad(registry)
+        .and(warp::path!("ramps"))
+        .and(warp::get())
+        .and_then(handlers::ramps)
+}
+
+/// PUT /pads/:id/ramps with the ramps as JSON, kept across restarts
+pub fn set_ramps<T: Transport>(
+    registry: Registry<T>,
+    dao: Arc<dyn Dao>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("ramps"))
+        .and(warp::put())
+        .and(warp::body::content_length_limit(1024))
+        .and(warp::body::json())
+        .and(with_dao(dao))
+        .and_then(handlers::set_ramps)
+}
+
+/// POST /pads/:id/!heartbeat
+pub fn heartbeat<T: Transport>(
+    registry: Registry<T>,
+    leases: Leases,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("!heartbeat"))
+        .and(warp::post())
+        .and(with_leases(leases))
+        .and_then(handlers::heartbeat)
+}
+
+/// GET /pads/:id/lease
+pub fn lease<T: Transport>(
+    registry: Registry<T>,
+    leases: Leases,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("lease"))
+        .and(warp::get())
+        .and(with_leases(leases))
+        .and_then(handlers::lease)
+}
+
+/// GET /pads/:id/walk_off
+pub fn walk_off<T: Transport>(
+    registry: Registry<T>,
+    walk_offs: WalkOffs,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("walk_off"))
+        .and(warp::get())
+        .and(warp::any().map(move || walk_offs.clone()))
+        .and_then(handlers::walk_off)
+}
+
+/// POST /pads/:id/program with the program as TOML
+pub fn start_program<T: Transport>(
+    registry: Registry<T>,
+    programs: Programs,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("program"))
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::body::bytes())
+        .and(with_programs(programs))
+        .and_then(handlers::start_program)
+}
+
+/// GET /pads/:id/program
+pub fn program<T: Transport>(
+    registry: Registry<T>,
+    programs: Programs,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("program"))
+        .and(warp::get())
+        .and(with_programs(programs))
+        .and_then(handlers::program)
+}
+
+/// POST /pads/:id/program/!pause, /!resume, /!skip or /!stop
+pub fn control_program<T: Transport>(
+    registry: Registry<T>,
+    programs: Programs,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry)
+        .and(warp::path!("program" / String))
+        .and(warp::post())
+        .and(with_programs(programs))
+        .and_then(handlers::control_program)
+}
+
+/// GET /sessions
+pub fn sessions(
+    dao: Arc<dyn Dao>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("sessions")
+        .and(warp::get())
+        .and(with_dao(dao))
+        .and_then(handlers::sessions)
+}
+
+/// GET /sessions/:id
+pub fn session(
+    dao: Arc<dyn Dao>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("sessions" / i64)
+        .and(warp::get())
+        .and(with_dao(dao))
+        .and_then(handlers::session)
+}
+
+/// GET /sessions/:id/tcx, /gpx or /fit
+pub fn export(
+    dao: Arc<dyn Dao>,
+    route: Option<Arc<Route>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("sessions" / i64 / Format)
+        .and(warp::get())
+        .and(with_dao(dao))
+        .and(warp::any().map(move || route.clone()))
+        .and_then(handlers::export)
+}
+
 // pub fn todos_list(
 //     pad: Pad<_>,
 // ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -94,10 +297,53 @@ pub fn change_speed<T: btleplug::api::Peripheral>(
 //         .and_then(handlers::delete_todo)
 // }
 
-fn with_pad<T: btleplug::api::Peripheral>(
-    pad: Pad<T>,
-) -> impl Filter<Extract = (Pad<T>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || pad.clone())
+/// Matches the `/pads/:id` prefix and extracts the pad, unknown ids are not found.
+fn with_pad<T: Transport>(
+    registry: Registry<T>,
+) -> impl Filter<Extract = (Pad<T>,), Error = warp::Rejection> + Clone {
+    with_id_and_pad(registry).map(|_, pad| pad)
+}
+
+/// Like `with_pad`, but extracts the id too.
+fn with_id_and_pad<T: Transport>(
+    registry: Registry<T>,
+) -> impl Filter<Extract = (String, Pad<T>), Error = warp::Rejection> + Clone {
+    warp::path("pads")
+        .and(warp::path::param::<String>())
+        .and_then(move |id: String| {
+            let pad = registry.get(&id);
+            async move {
+                match pad {
+                    Some(pad) => Ok((id, pad)),
+                    None => Err(warp::reject::not_found()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+fn with_dao(
+    dao: Arc<dyn Dao>,
+) -> impl Filter<Extract = (Arc<dyn Dao>,), Error = Infallible> + Clone {
+    warp::any().map(move || dao.clone())
+}
+
+fn with_leases(leases: Leases) -> impl Filter<Extract = (Leases,), Error = Infallible> + Clone {
+    warp::any().map(move || leases.clone())
+}
+
+/// Renews the lease of the pad on the way to the handler.
+fn renewing<T: Transport>(leases: Leases) -> impl Fn(String, Pad<T>) -> Pad<T> + Clone {
+    move |id, pad| {
+        leases.renew(&id);
+        pad
+    }
+}
+
+fn with_programs(
+    programs: Programs,
+) -> impl Filter<Extract = (Programs,), Error = Infallible> + Clone {
+    warp::any().map(move || programs.clone())
 }
 
 // fn content_length() -> impl Filter<Extract = (,), Error = warp::Rejection> + Clone {
@@ -115,7 +361,7 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     } else if let Some(e) = err.find::<crate::http::handlers::Error>() {
         code = StatusCode::BAD_REQUEST;
         message = &e.reason;
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         // We can handle a specific error, here METHOD_NOT_ALLOWED,
         // and render it however we want
         code = StatusCode::METHOD_NOT_ALLOWED;
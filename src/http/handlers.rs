@@ -1,11 +1,21 @@
-use crate::Pad;
+use crate::controller::enums::{BeltState, ConnectionEvent, Mode, Units};
+use crate::controller::ramp::{Ramp, Ramps};
+use crate::controller::registry::Registry;
+use crate::controller::units::Speed;
+use crate::controller::{Pad, State, Transport};
+use crate::dao::{Dao, DaoError};
+use crate::export::{self, Format, Route};
+use crate::profile::Profile;
+use crate::program::{Program, Programs};
+use crate::safety::{Leases, WalkOffs};
 use std::collections::HashMap;
 use std::convert::Infallible;
-use std::fmt;
-use warp::http::StatusCode;
+use std::sync::Arc;
+use std::time::Duration;
 
+use log::warn;
 use serde::Serialize;
-use warp::{reject, Rejection};
+use warp::{hyper::StatusCode, reject, Rejection};
 
 #[derive(Debug, Serialize)]
 pub struct Error {
@@ -14,52 +24,381 @@ pub struct Error {
 
 impl warp::reject::Reject for Error {}
 
-pub async fn start_belt<T: btleplug::api::Peripheral>(
+const MAX_SPEED: Speed = Speed::from_tenths_kmh(60);
+
+/// `State` in the units asked for with `?units=metric|imperial`.
+#[derive(Debug, Serialize)]
+pub struct StateReply {
+    pub belt_state: BeltState,
+    pub mode: Mode,
+    pub speed: f64,
+    pub last_speed: f64,
+    pub speed_unit: &'static str,
+    pub distance: f64,
+    pub distance_unit: &'static str,
+    /// Seconds.
+    pub time: u32,
+    pub steps: usize,
+    /// kcal, only with a profile.
+    pub calories: Option<f64>,
+}
+
+impl StateReply {
+    pub fn new(state: &State, units: Units, profile: Option<&Profile>) -> Self {
+        let (speed, last_speed, distance) = match units {
+            Units::Metric => (
+                state.speed.kmh(),
+                state.last_speed.kmh(),
+                state.distance.km(),
+            ),
+            Units::Imperial => (
+                state.speed.mph(),
+                state.last_speed.mph(),
+                state.distance.miles(),
+            ),
+        };
+
+        StateReply {
+            belt_state: state.belt_state,
+            mode: state.mode,
+            speed: round(speed, 2),
+            last_speed: round(last_speed, 2),
+            speed_unit: units.speed_unit(),
+            distance: round(distance, 3),
+            distance_unit: units.distance_unit(),
+            time: state.time.secs(),
+            steps: state.steps,
+            calories: profile.map(|profile| round(profile.calories(state.distance, state.time), 1)),
+        }
+    }
+}
+
+fn round(value: f64, places: i32) -> f64 {
+    let scale = 10f64.powi(places);
+    (value * scale).round() / scale
+}
+
+/// Units from the query, metric by default.
+fn units(query: &HashMap<String, String>) -> Result<Units, Rejection> {
+    match query.get("units") {
+        Some(units) => units
+            .parse()
+            .map_err(|reason| reject::custom(Error { reason })),
+        None => Ok(Units::Metric),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PadInfo {
+    pub id: String,
+    pub connection: ConnectionEvent,
+}
+
+pub async fn pads<T: Transport>(registry: Registry<T>) -> Result<impl warp::Reply, Infallible> {
+    let pads: Vec<_> = registry
+        .iter()
+        .map(|(id, pad)| PadInfo {
+            id: id.clone(),
+            connection: pad.connection(),
+        })
+        .collect();
+    Ok(warp::reply::json(&pads))
+}
+
+pub async fn start_belt<T: Transport>(pad: Pad<T>) -> Result<impl warp::Reply, Rejection> {
+    match pad.start_belt().await {
+        Ok(_) => Ok(warp::reply::json(&"Belt Started!".to_string())),
+        Err(err) => Err(reject::custom(Error {
+            reason: format!("There was some internal error! {}", err),
+        })),
+    }
+}
+
+/// With a cool-down the reply comes while the belt still slows down, which it
+/// says with "Belt Stopping!".
+pub async fn stop_belt<T: Transport>(pad: Pad<T>) -> Result<impl warp::Reply, Rejection> {
+    match pad.stop_belt().await {
+        Ok(true) => Ok(warp::reply::json(&"Belt Stopping!".to_string())),
+        Ok(false) => Ok(warp::reply::json(&"Belt Stopped!".to_string())),
+        Err(err) => Err(reject::custom(Error {
+            reason: format!("There was some internal error! {}", err),
+        })),
+    }
+}
+
+/// The speed is given in `kmh` or `mph`, or in tenths of km/h as `speed` like
+/// before those. Without any the belt stops. With `ramp` the change is spread
+/// over that many seconds.
+pub async fn change_speed<T: Transport>(
     pad: Pad<T>,
-) -> Result<impl warp::Reply, Infallible> {
-    pad.start_belt().await;
-    Ok(warp::reply::json(&"Belt Started!".to_string()))
+    query: HashMap<String, String>,
+) -> Result<impl warp::Reply, Rejection> {
+    let speed = requested_speed(&query)?;
+    let ramp = match query.get("ramp").map(|secs| secs.parse::<f64>()) {
+        Some(Ok(secs)) if secs.is_finite() && secs >= 0.0 => Some(Duration::from_secs_f64(secs)),
+        None => None,
+        Some(_) => {
+            return Err(reject::custom(Error {
+                reason: "Ramp not allowed!".to_string(),
+            }))
+        }
+    };
+    if let Some(ramp) = ramp {
+        // The ramp runs on, a later change cancels it.
+        tokio::spawn(async move {
+            if let Err(err) = pad.ramp_to(speed, Ramp::Over(ramp)).await {
+                warn!("Ramp to {} failed: {}", speed, err);
+            }
+        });
+        return Ok(warp::reply::json(&format!("Ramping to {}", speed)));
+    }
+    match pad.change_speed(speed).await {
+        Ok(_) => Ok(warp::reply::json(
+            &format!("Speed changed to {}", speed).to_string(),
+        )),
+        Err(err) => Err(reject::custom(Error {
+            reason: format!("There was some internal error! {}", err),
+        })),
+    }
+}
+
+fn requested_speed(query: &HashMap<String, String>) -> Result<Speed, Rejection> {
+    let not_allowed = |reason: String| reject::custom(Error { reason });
+    let speed = match (query.get("kmh"), query.get("mph"), query.get("speed")) {
+        (None, None, None) => Speed::ZERO,
+        (Some(value), None, None) => Speed::from_kmh(decimal(value)?),
+        (None, Some(value), None) => Speed::from_mph(decimal(value)?),
+        (None, None, Some(tenths)) => match tenths.parse::<u8>() {
+            Ok(tenths) => Speed::from_tenths_kmh(tenths),
+            Err(_) => {
+                return Err(not_allowed(format!(
+                    "Speed not allowed! {} (speed is in tenths of km/h, kmh takes km/h)",
+                    tenths
+                )))
+            }
+        },
+        _ => return Err(not_allowed("Expected one of kmh, mph or speed".to_string())),
+    };
+
+    if speed > MAX_SPEED {
+        Err(not_allowed(format!("Speed not allowed! {}", speed)))
+    } else {
+        Ok(speed)
+    }
+}
+
+/// A speed in km/h or mph, finite and not negative.
+fn decimal(value: &str) -> Result<f64, Rejection> {
+    match value.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        _ => Err(reject::custom(Error {
+            reason: format!("Speed not allowed! {}", value),
+        })),
+    }
+}
+
+pub async fn state<T: Transport>(
+    pad: Pad<T>,
+    query: HashMap<String, String>,
+    profile: Option<Profile>,
+) -> Result<impl warp::Reply, Rejection> {
+    let units = units(&query)?;
+    match pad.state() {
+        Some(state) => Ok(warp::reply::json(&StateReply::new(
+            &state,
+            units,
+            profile.as_ref(),
+        ))),
+        None => Err(reject::custom(Error {
+            reason: "No state received from the pad yet!".to_string(),
+        })),
+    }
+}
+
+pub async fn connection<T: Transport>(pad: Pad<T>) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::json(&pad.connection()))
 }
 
-pub async fn stop_belt<T: btleplug::api::Peripheral>(
+pub async fn ramps<T: Transport>(pad: Pad<T>) -> Result<impl warp::Reply, Infallible> {
+    Ok(warp::reply::json(&pad.ramps()))
+}
+
+/// Applies the ramps at once and saves them, they win over the config from then on.
+pub async fn set_ramps<T: Transport>(
+    id: String,
     pad: Pad<T>,
+    ramps: Ramps,
+    dao: Arc<dyn Dao>,
+) -> Result<impl warp::Reply, Rejection> {
+    if let Some(speed) = ramps.warm_up_to.filter(|speed| *speed > MAX_SPEED) {
+        return Err(reject::custom(Error {
+            reason: format!("Speed not allowed! {}", speed.kmh()),
+        }));
+    }
+
+    dao.update_ramps(&id, &ramps).await.map_err(dao_error)?;
+    pad.set_ramps(ramps);
+    Ok(warp::reply::json(&ramps))
+}
+
+/// Renews the lease of the pad and replies with it.
+pub async fn heartbeat<T: Transport>(
+    id: String,
+    _pad: Pad<T>,
+    leases: Leases,
+) -> Result<impl warp::Reply, Rejection> {
+    match leases.renew(&id) {
+        Some(lease) => Ok(warp::reply::json(&lease)),
+        None => Err(reject::custom(Error {
+            reason: "The pad needs no heartbeat!".to_string(),
+        })),
+    }
+}
+
+pub async fn lease<T: Transport>(
+    id: String,
+    _pad: Pad<T>,
+    leases: Leases,
+) -> Result<impl warp::Reply, Rejection> {
+    match leases.get(&id) {
+        Some(lease) => Ok(warp::reply::json(&lease.state())),
+        None => Err(reject::not_found()),
+    }
+}
+
+/// The last walk-off on the pad, null before the first.
+pub async fn walk_off<T: Transport>(
+    id: String,
+    _pad: Pad<T>,
+    walk_offs: WalkOffs,
 ) -> Result<impl warp::Reply, Infallible> {
-    pad.stop_belt().await;
-    Ok(warp::reply::json(&"Belt Stopped!".to_string()))
+    Ok(warp::reply::json(&walk_offs.get(&id)))
 }
 
-pub async fn change_speed<T: btleplug::api::Peripheral>(
-    query: HashMap<String, String>,
+/// Starts the program in the body on the pad and replies with its progress.
+pub async fn start_program<T: Transport>(
+    id: String,
     pad: Pad<T>,
+    body: warp::hyper::body::Bytes,
+    programs: Programs,
 ) -> Result<impl warp::Reply, Rejection> {
-    let speed = match query.get("speed") {
-        Some(spd) => spd.parse(),
-        None => Ok(0),
-    };
+    let program = std::str::from_utf8(&body)
+        .map_err(|err| err.to_string())
+        .and_then(|toml| Program::parse(toml).map_err(|err| err.details))
+        .map_err(|reason| reject::custom(Error { reason }))?;
 
-    match speed {
-        Ok(speed) => {
-            if speed > 60 || speed < 0 {
-                Err(reject::custom(Error {
-                    reason: format!("Speed not allowed! {}", speed),
-                }))
-            } else {
-                match pad.change_speed(speed as u8).await {
-                    Ok(_) => Ok(warp::reply::json(
-                        &format!("Speed changed to {}", speed).to_string(),
-                    )),
-                    Err(err) => Err(reject::custom(Error {
-                        reason: format!("There was some internal error! {}", err.to_string()),
-                    })),
-                }
-            }
-        }
-        Err(_) => Err(reject::custom(Error {
-            reason: format!("Speed not provided!"),
+    match programs.start(&id, &pad, program) {
+        Ok(runner) => Ok(warp::reply::json(&runner.progress())),
+        Err(err) => Err(reject::custom(Error {
+            reason: err.details,
         })),
     }
 }
 
+/// Progress of the program running on the pad, or of the last one that ran.
+pub async fn program<T: Transport>(
+    id: String,
+    _pad: Pad<T>,
+    programs: Programs,
+) -> Result<impl warp::Reply, Rejection> {
+    match programs.get(&id) {
+        Some(runner) => Ok(warp::reply::with_status(
+            warp::reply::json(&runner.progress()),
+            StatusCode::OK,
+        )),
+        None => Ok(no_program()),
+    }
+}
+
+pub async fn control_program<T: Transport>(
+    id: String,
+    _pad: Pad<T>,
+    action: String,
+    programs: Programs,
+) -> Result<impl warp::Reply, Rejection> {
+    let runner = match programs.get(&id) {
+        Some(runner) => runner,
+        None => return Ok(no_program()),
+    };
+    let reply = match action.as_str() {
+        "!pause" => {
+            runner.pause();
+            "Program paused!"
+        }
+        "!resume" => {
+            runner.resume();
+            "Program resumed!"
+        }
+        "!skip" => {
+            runner.skip();
+            "Segment skipped!"
+        }
+        "!stop" => {
+            runner.stop();
+            "Program stopped!"
+        }
+        _ => return Err(reject::not_found()),
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&reply.to_string()),
+        StatusCode::OK,
+    ))
+}
+
+/// Not found as a reply rather than a rejection, which the other methods on
+/// the same path would win over.
+fn no_program() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&Error {
+            reason: "No program ran on the pad yet!".to_string(),
+        }),
+        StatusCode::NOT_FOUND,
+    )
+}
+
+fn dao_error(err: DaoError) -> Rejection {
+    reject::custom(Error {
+        reason: format!("There was some internal error! {}", err),
+    })
+}
+
+/// Stored sessions, latest first, without their samples.
+pub async fn sessions(dao: Arc<dyn Dao>) -> Result<impl warp::Reply, Rejection> {
+    let sessions = dao.read_sessions().await.map_err(dao_error)?;
+    Ok(warp::reply::json(&sessions))
+}
+
+pub async fn session(id: i64, dao: Arc<dyn Dao>) -> Result<impl warp::Reply, Rejection> {
+    match dao.read_session(id).await.map_err(dao_error)? {
+        Some(session) => Ok(warp::reply::json(&session)),
+        None => Err(reject::not_found()),
+    }
+}
+
+/// The session as an activity file to download.
+pub async fn export(
+    id: i64,
+    format: Format,
+    dao: Arc<dyn Dao>,
+    route: Option<Arc<Route>>,
+) -> Result<impl warp::Reply, Rejection> {
+    let stored = match dao.read_session(id).await.map_err(dao_error)? {
+        Some(stored) => stored,
+        None => return Err(reject::not_found()),
+    };
+
+    let file = export::export(&stored.session, format, route.as_deref());
+    let disposition = format!(
+        "attachment; filename=\"session-{}.{}\"",
+        id,
+        format.extension()
+    );
+    Ok(warp::http::Response::builder()
+        .header("Content-Type", format.content_type())
+        .header("Content-Disposition", disposition)
+        .body(file))
+}
+
 // pub async fn create_todo(create: Todo, db: Db) -> Result<impl warp::Reply, Infallible> {
 //     log::debug!("create_todo: {:?}", create);
 
@@ -0,0 +1,13 @@
+pub mod btsnoop;
+pub mod config;
+pub mod controller;
+pub mod dao;
+pub mod discovery;
+pub mod export;
+pub mod http;
+pub mod profile;
+pub mod program;
+pub mod recording;
+pub mod safety;
+pub mod session;
+pub mod simulator;
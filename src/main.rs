@@ -1,33 +1,173 @@
 // See the "macOS permissions note" in README.md before running this on macOS
 // Big Sur or later.
 
-use btleplug::api::{Central, Characteristic, Manager as _, Peripheral, ScanFilter};
-use btleplug::platform::Manager;
-extern crate derive_more;
-use derive_more::{Add, Display, Error as DError, From, Into};
-use futures::select;
+use btleplug::api::{BDAddr, Peripheral};
+use btleplug::platform::{Adapter, Manager, Peripheral as PlatformPeripheral, PeripheralId};
+use chrono::Utc;
 use futures::StreamExt;
 use std::error::Error;
-use std::path::PathBuf;
-use std::pin::Pin;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use structopt::StructOpt;
-use tokio::time;
+use tokio::task::JoinHandle;
 use warp::Filter;
 
 use signal_hook::consts::signal::*;
 use signal_hook_tokio::Signals;
 
-mod controller;
-use controller::*;
-
-mod http;
-use http::filters::*;
+use walkingpad::btsnoop;
+use walkingpad::config::Config;
+use walkingpad::controller::registry::Registry;
+use walkingpad::controller::{self, *};
+use walkingpad::dao::{Dao, SqliteDao};
+use walkingpad::discovery::{self, AdapterSelector, DiscoveryOptions};
+use walkingpad::export::{self, Format, Route};
+use walkingpad::http::{self, filters::Context};
+use walkingpad::program::Programs;
+use walkingpad::recording::{self, Recorder, Replay};
+use walkingpad::safety::lease::{self, Lease, Leases};
+use walkingpad::safety::walk_off::{self, WalkOffs};
+use walkingpad::session::{self, Session, SessionEvent};
+use walkingpad::simulator::Simulator;
 
 extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+#[derive(Debug, StructOpt)]
+#[structopt(name = "walkingpad", about = "Controls a WalkingPad over BLE")]
+struct Opt {
+    /// Bluetooth adapter, by index or by a part of its name
+    #[structopt(long, default_value = "0")]
+    adapter: AdapterSelector,
+
+    /// Case-insensitive part of the pad name, empty to match any name
+    #[structopt(long, default_value = "walkingpad")]
+    name: String,
+
+    /// MAC address of the pad when driving a single one
+    #[structopt(long)]
+    address: Option<BDAddr>,
+
+    /// Only match pads advertising the FE00 service
+    #[structopt(long)]
+    service: bool,
+
+    /// How long to scan for pads, in seconds
+    #[structopt(long, default_value = "5")]
+    scan_timeout: u64,
+
+    /// List the matching pads with their RSSI and exit
+    #[structopt(long)]
+    list: bool,
+
+    /// Pad to drive as `id=MAC address`, can be repeated. Without any, the
+    /// strongest matching pad is driven as `default`.
+    #[structopt(long = "pad")]
+    pads: Vec<PadSpec>,
+
+    /// Drive simulated pads instead of scanning, one per `--pad` id
+    #[structopt(long)]
+    simulate: bool,
+
+    /// Record the traffic of every pad to `<dir>/<id>.jsonl`
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Replay a recording instead of scanning
+    #[structopt(long, parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// How many times faster than recorded to replay
+    #[structopt(long, default_value = "1")]
+    replay_speed: f64,
+
+    /// Seconds the belt may stand still before the session ends
+    #[structopt(long, default_value = "300")]
+    pause_timeout: u64,
+
+    /// SQLite database keeping the sessions
+    #[structopt(long, parse(from_os_str), default_value = "walkingpad.sqlite")]
+    database: PathBuf,
+
+    /// TOML configuration, `walkingpad.toml` if there is one
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// GPX file with the route that exported GPX tracks follow
+    #[structopt(long, global = true, parse(from_os_str))]
+    route: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    tool: Option<Tool>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Tool {
+    /// Prints the annotated timeline of a btsnoop capture or a recording
+    Decode {
+        #[structopt(parse(from_os_str))]
+        capture: PathBuf,
+
+        /// Also save the pad traffic as a recording, e.g. for `--replay`
+        #[structopt(long, parse(from_os_str))]
+        save: Option<PathBuf>,
+    },
+    /// Lists the sessions kept in the database
+    Sessions,
+    /// Saves a session as an activity file
+    Export {
+        /// Id of the session, see `sessions`
+        id: i64,
+
+        /// tcx, gpx or fit
+        #[structopt(long, default_value = "tcx")]
+        format: Format,
+
+        /// Where to save it, `session-<id>.<format>` by default
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Opt {
+    /// Ids of the pads given with `--pad`, or just `default`.
+    fn ids(&self) -> Vec<String> {
+        if self.pads.is_empty() {
+            vec!["default".to_string()]
+        } else {
+            self.pads.iter().map(|spec| spec.id.clone()).collect()
+        }
+    }
+}
+
+const DEFAULT_CONFIG: &str = "walkingpad.toml";
+
+/// Pads to drive by their id.
+type Transports = Vec<(String, Box<dyn Transport>)>;
+
+#[derive(Debug)]
+struct PadSpec {
+    id: String,
+    address: BDAddr,
+}
+
+impl FromStr for PadSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, address) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected id=address, got {}", s))?;
+        Ok(PadSpec {
+            id: id.to_string(),
+            address: address.parse().map_err(|e| format!("{}", e))?,
+        })
+    }
+}
+
 async fn handle_signals(mut signals: Signals) {
     while let Some(signal) = signals.next().await {
         match signal {
@@ -45,102 +185,383 @@ async fn handle_signals(mut signals: Signals) {
 async fn main() -> Result<(), Box<dyn Error>> {
     pretty_env_logger::init_timed();
 
-    let signals = Signals::new(&[SIGHUP, SIGTERM, SIGINT, SIGQUIT])?;
-    let handle = signals.handle();
-    let signals_task = tokio::spawn(handle_signals(signals));
+    let opt = Opt::from_args();
+    if let Some(Tool::Decode { capture, save }) = &opt.tool {
+        return decode(capture, save.as_deref());
+    }
 
-    let manager = Manager::new().await?;
-    let adapter_list = manager.adapters().await?;
-    if adapter_list.is_empty() {
-        eprintln!("No Bluetooth adapters found");
+    let config = match &opt.config {
+        Some(path) => Config::read(path)?,
+        None if Path::new(DEFAULT_CONFIG).exists() => Config::read(Path::new(DEFAULT_CONFIG))?,
+        None => Config::default(),
+    };
+    let context = Context {
+        dao: Arc::new(SqliteDao::open(&opt.database)?),
+        route: match &opt.route {
+            Some(path) => Some(Arc::new(Route::read(path)?)),
+            None => None,
+        },
+        profile: config.profile,
+        programs: Programs::default(),
+        leases: Leases::default(),
+        walk_offs: WalkOffs::default(),
+    };
+    if context.profile.is_none() {
+        info!("No profile configured, calories are not estimated");
     }
 
-    let adapter = adapter_list[0].clone();
+    match &opt.tool {
+        Some(Tool::Sessions) => return list_sessions(context.dao.as_ref()).await,
+        Some(Tool::Export { id, format, output }) => {
+            let route = context.route.as_deref();
+            return export_session(context.dao.as_ref(), *id, *format, output.clone(), route).await;
+        }
+        _ => {}
+    }
 
-    info!("Starting scan on {}...", adapter.adapter_info().await?);
-    adapter
-        .start_scan(ScanFilter::default())
-        .await
-        .expect("Can't scan BLE adapter for connected devices...");
+    let signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT])?;
+    let handle = signals.handle();
+    let signals_task = tokio::spawn(handle_signals(signals));
 
-    std::thread::sleep(time::Duration::from_secs(1));
-    let peripherals = adapter.peripherals().await?;
-    if peripherals.is_empty() {
-        eprintln!("->>> BLE peripheral devices were not found, sorry. Exiting...");
+    let mut transports: Transports = vec![];
+    if let Some(path) = &opt.replay {
+        let id = opt.ids().swap_remove(0);
+        info!("Replaying {} as {}", path.display(), id);
+        transports.push((id, Box::new(Replay::open(path, opt.replay_speed)?)));
+    } else if opt.simulate {
+        for id in opt.ids() {
+            info!("Simulating {}", id);
+            transports.push((id, Box::new(Simulator::default())));
+        }
     } else {
-        let x = async {
-            let mut res: Option<_> = Option::None;
-            for peripheral in peripherals.iter() {
-                let properties = peripheral.properties().await;
-                match properties {
-                    Ok(prop) => {
-                        let local_name = prop
-                            .unwrap()
-                            .local_name
-                            .unwrap_or(String::from("(peripheral name unknown)"));
-                        if local_name.to_lowercase().contains("walkingpad") {
-                            res = Option::Some(peripheral.clone());
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        info!("ERROR {}", e);
-                    }
+        match discover(&opt).await? {
+            Some(discovered) => transports = discovered,
+            None => return Ok(()),
+        }
+    }
+
+    let mut registry = Registry::default();
+    let mut sessions_tasks = vec![];
+    let pause_timeout = Duration::from_secs(opt.pause_timeout);
+    for (id, transport) in transports {
+        let transport: Box<dyn Transport> = match &opt.record {
+            Some(dir) => match Recorder::create(transport, &dir.join(format!("{}.jsonl", id))) {
+                Ok(recorder) => Box::new(recorder),
+                Err(e) => {
+                    error!("Recording {} failed, skipping it: {}", id, e);
+                    continue;
                 }
+            },
+            None => transport,
+        };
+        let pad = match Pad::new(transport).await {
+            Ok(pad) => pad,
+            Err(e) => {
+                error!("Connecting to {} failed, skipping it: {}", id, e);
+                continue;
             }
-            res
         };
+        match start(&id, &pad, pause_timeout, &context).await {
+            Ok(sessions_task) => sessions_tasks.push(sessions_task),
+            Err(e) => {
+                error!("Starting {} failed, skipping it: {}", id, e);
+                if let Err(e) = pad.disconnect().await {
+                    warn!("Disconnecting {} failed: {}", id, e);
+                }
+                continue;
+            }
+        }
 
-        let walkingpad = x.await;
-
-        if let Some(walkingpad) = walkingpad {
-            let pad = Pad::new(&walkingpad).await?;
-
-            let api = http::filters::walkingpad(pad.clone());
+        match context.dao.read_ramps(&id).await {
+            Ok(ramps) => pad.set_ramps(ramps.unwrap_or(config.ramps)),
+            Err(e) => {
+                warn!("Reading the ramps of {} failed: {}", id, e);
+                pad.set_ramps(config.ramps);
+            }
+        }
+        if let Some(secs) = config.safety.lease_secs {
+            let lease = Lease::new(Duration::from_secs(secs));
+            context.leases.insert(&id, lease.clone());
+            lease::guard(&id, &pad, lease, context.programs.clone());
+        }
+        if !config.safety.walk_off.is_empty() {
+            let mut walk_offs = walk_off::watch(
+                &id,
+                &pad,
+                config.safety.walk_off.clone(),
+                context.programs.clone(),
+                context.walk_offs.clone(),
+            );
+            let id = id.clone();
             tokio::spawn(async move {
-                let routes = api.with(warp::log("walkingpad"));
-                warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+                while let Some(walk_off) = walk_offs.recv().await {
+                    info!("Walk-off on {}: {:?}", id, walk_off);
+                }
             });
-            pad.services().await;
+        }
+        registry.insert(id, pad);
+    }
 
-            pad.subs().await?;
+    if registry.is_empty() {
+        info!("Not found.");
+        return Ok(());
+    }
 
-            let k = pad.gets().await?;
-            tokio::spawn(async move {
-                k.for_each(|data| async move {
-                    let res = State::new(data.value);
-                    info!("Received data [{:?}]: {:?}", data.uuid, res)
-                })
-                .await
-            });
+    serve(registry, context, signals_task, sessions_tasks).await?;
+    handle.close();
 
-            info!("connected {}", walkingpad.is_connected().await?);
-            // pad.start_belt().await?;
-            pad.switch_mode(controller::enums::Mode::Manual).await?;
+    Ok(())
+}
 
-            let pad_clone = pad.clone();
-            let j = tokio::spawn(async move {
-                loop {
-                    pad_clone.ask_stats().await;
-                    tokio::time::sleep(Duration::from_millis(750)).await;
-                }
-            });
+/// Prints the timeline of a btsnoop capture or of one of our recordings.
+fn decode(capture: &Path, save: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(capture)?;
+    let events = if btsnoop::is_btsnoop(&data) {
+        btsnoop::parse(&data)?
+    } else {
+        recording::read(capture)?
+    };
 
-            // pad.switch_mode(Mode::Manual).await?;
-            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-            pad.change_speed(25).await?;
+    for line in recording::timeline(&events) {
+        println!("{}", line);
+    }
+    if let Some(save) = save {
+        recording::write(save, &events)?;
+    }
 
-            signals_task.await?;
-            j.abort();
-            // walkingpad.connect().await?;
-            // pad.stop_belt().await?;
+    Ok(())
+}
 
-            pad.stop_belt().await?;
-            pad.disconnect().await;
+async fn list_sessions(dao: &dyn Dao) -> Result<(), Box<dyn Error>> {
+    for stored in dao.read_sessions().await? {
+        let session = &stored.session;
+        let calories = session
+            .calories
+            .map_or(String::new(), |kcal| format!(", {:.0} kcal", kcal));
+        // The pad does not date its history, so imported sessions have no real start.
+        let imported = if stored.imported {
+            ", from the pad history"
         } else {
-            info!("Not found.")
+            ""
+        };
+        println!(
+            "{:>5} {} {} {} {} {} steps, {} on average{}{}",
+            stored.id,
+            session.started_at.format("%Y-%m-%d %H:%M"),
+            stored.pad,
+            session.time,
+            session.distance,
+            session.steps,
+            session.average_speed,
+            calories,
+            imported
+        );
+    }
+
+    Ok(())
+}
+
+async fn export_session(
+    dao: &dyn Dao,
+    id: i64,
+    format: Format,
+    output: Option<PathBuf>,
+    route: Option<&Route>,
+) -> Result<(), Box<dyn Error>> {
+    let stored = dao
+        .read_session(id)
+        .await?
+        .ok_or_else(|| format!("No session {}", id))?;
+    let output =
+        output.unwrap_or_else(|| PathBuf::from(format!("session-{}.{}", id, format.extension())));
+
+    std::fs::write(&output, export::export(&stored.session, format, route))?;
+    info!("Saved session {} to {}", id, output.display());
+
+    Ok(())
+}
+
+/// Scans for the pads to drive, `None` when they were only listed.
+async fn discover(opt: &Opt) -> Result<Option<Transports>, Box<dyn Error>> {
+    let options = DiscoveryOptions {
+        adapter: opt.adapter.clone(),
+        name: Some(opt.name.clone()).filter(|name| !name.is_empty()),
+        address: opt.address,
+        service: opt.service,
+        timeout: Duration::from_secs(opt.scan_timeout),
+    };
+
+    let manager = Manager::new().await?;
+    let adapter = discovery::select_adapter(&manager, &options.adapter).await?;
+
+    let candidates = discovery::discover(&adapter, &options).await?;
+    if opt.list {
+        for candidate in candidates {
+            println!(
+                "{} {} RSSI {}",
+                candidate.address,
+                candidate.name.unwrap_or_default(),
+                candidate
+                    .rssi
+                    .map_or("unknown".to_string(), |rssi| rssi.to_string())
+            );
+        }
+        return Ok(None);
+    }
+
+    let mut chosen = vec![];
+    if opt.pads.is_empty() {
+        chosen.extend(
+            candidates
+                .into_iter()
+                .next()
+                .map(|candidate| ("default".to_string(), candidate)),
+        );
+    } else {
+        for spec in &opt.pads {
+            match candidates.iter().find(|c| c.address == spec.address) {
+                Some(candidate) => chosen.push((spec.id.clone(), candidate.clone())),
+                None => warn!("Pad {} ({}) not found", spec.id, spec.address),
+            }
+        }
+    }
+
+    let mut transports: Transports = vec![];
+    for (id, candidate) in chosen {
+        info!("Using {} {} {:?}", id, candidate.address, candidate.name);
+        let rescan = rescan(adapter.clone(), options.clone(), candidate.peripheral.id());
+        let transport = BtleTransport::with_rescan(candidate.peripheral, rescan);
+        transports.push((id, Box::new(transport)));
+    }
+
+    Ok(Some(transports))
+}
+
+/// Serves the HTTP API until a signal arrives, then stops every belt at once and
+/// waits until the sessions in progress are saved. A pad failing to stop does
+/// not keep the others running.
+async fn serve<T: Transport>(
+    registry: Registry<T>,
+    context: Context,
+    signals_task: JoinHandle<()>,
+    sessions_tasks: Vec<JoinHandle<()>>,
+) -> Result<(), Box<dyn Error>> {
+    let api = http::filters::walkingpad(registry.clone(), context);
+    tokio::spawn(async move {
+        let routes = api.with(warp::log("walkingpad"));
+        warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+    });
+
+    signals_task.await?;
+
+    let stops = registry.iter().map(|(id, pad)| async move {
+        if let Err(e) = pad.stop_belt_verified().await {
+            error!("Stopping belt of {} failed: {}", id, e);
+        }
+        if let Err(e) = pad.disconnect().await {
+            error!("Disconnecting {} failed: {}", id, e);
+        }
+    });
+    futures::future::join_all(stops).await;
+    for task in sessions_tasks {
+        if let Err(e) = task.await {
+            error!("Saving the sessions failed: {}", e);
         }
     }
 
     Ok(())
 }
+
+/// Looks the pad up again by its id when it has to reconnect.
+fn rescan(
+    adapter: Adapter,
+    options: DiscoveryOptions,
+    id: PeripheralId,
+) -> Rescan<PlatformPeripheral> {
+    Arc::new(move || {
+        let adapter = adapter.clone();
+        let options = options.clone();
+        let id = id.clone();
+        Box::pin(async move {
+            discovery::discover(&adapter, &options)
+                .await
+                .map_err(|e| btleplug::Error::Other(Box::new(e)))?
+                .into_iter()
+                .map(|candidate| candidate.peripheral)
+                .find(|peripheral| peripheral.id() == id)
+                .ok_or(btleplug::Error::DeviceNotFound)
+        })
+    })
+}
+
+/// Stores the records of the pad history as sessions, the ones already stored
+/// are skipped.
+async fn import_history(id: &str, history: &[HistoryRecord], context: &Context) {
+    let fetched_at = Utc::now();
+    let mut imported = 0;
+    for record in history {
+        let session = Session::from_history(record, fetched_at, context.profile.as_ref());
+        match context.dao.import_session(id, &session).await {
+            Ok(Some(_)) => imported += 1,
+            Ok(None) => {}
+            Err(e) => error!("Saving history record of {} failed: {}", id, e),
+        }
+    }
+    info!(
+        "Fetched {} history records of {}, {} new",
+        history.len(),
+        id,
+        imported
+    );
+}
+
+async fn start<T: Transport>(
+    id: &str,
+    pad: &Pad<T>,
+    pause_timeout: Duration,
+    context: &Context,
+) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    match pad.fetch_history().await {
+        Ok(history) => import_history(id, &history, context).await,
+        Err(e) => warn!("Fetching history of {} failed: {}", id, e),
+    }
+
+    let logged = id.to_string();
+    let k = pad.register().into_stream();
+    tokio::spawn(async move {
+        k.for_each(|res| {
+            let id = logged.clone();
+            async move { info!("Received data [{}]: {:?}", id, res) }
+        })
+        .await
+    });
+
+    let id = id.to_string();
+    let dao = context.dao.clone();
+    let mut sessions = session::track(pad, pause_timeout, context.profile.clone());
+    let sessions_task = tokio::spawn(async move {
+        while let Some(event) = sessions.recv().await {
+            match event {
+                SessionEvent::Ended(session) => {
+                    info!(
+                        "Session of {} ended: {} and {} steps in {}, {} on average, {} at most",
+                        id,
+                        session.distance,
+                        session.steps,
+                        session.time,
+                        session.average_speed,
+                        session.max_speed
+                    );
+                    if let Err(e) = dao.create_session(&id, &session).await {
+                        error!("Saving session of {} failed: {}", id, e);
+                    }
+                }
+                event => info!("Session of {}: {:?}", id, event),
+            }
+        }
+    });
+
+    pad.switch_mode(controller::enums::Mode::Manual).await?;
+
+    Ok(sessions_task)
+}
@@ -0,0 +1,64 @@
+//! Energy spent walking, estimated from the profile of the walker.
+//!
+//! The oxygen uptake comes from the ACSM walking equation on a flat belt,
+//! `VO2 = 0.1 * speed + 3.5` in ml/kg/min with the speed in m/min, so that it
+//! is gross and includes resting. One MET, 3.5 ml/kg/min, is then worth the
+//! resting metabolic rate of the walker after Mifflin-St Jeor rather than the
+//! textbook 1 kcal/kg/h, which is where height, age and sex come in.
+
+use crate::controller::units::{Distance, Elapsed, Speed};
+
+use serde::{Deserialize, Serialize};
+
+/// ml/kg/min, one MET.
+const RESTING_VO2: f64 = 3.5;
+/// ml/kg per metre walked on the flat.
+const HORIZONTAL_VO2: f64 = 0.1;
+const MINUTES_PER_DAY: f64 = 1440.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+    Female,
+    Male,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub weight_kg: f64,
+    pub height_cm: f64,
+    pub age: u32,
+    pub sex: Sex,
+}
+
+impl Profile {
+    /// Mifflin-St Jeor, in kcal per day.
+    pub fn resting_kcal_per_day(&self) -> f64 {
+        let offset = match self.sex {
+            Sex::Female => -161.0,
+            Sex::Male => 5.0,
+        };
+        10.0 * self.weight_kg + 6.25 * self.height_cm - 5.0 * self.age as f64 + offset
+    }
+
+    /// Metabolic equivalent of walking at `speed`.
+    pub fn mets(speed: Speed) -> f64 {
+        let metres_per_minute = speed.kmh() * 1000.0 / 60.0;
+        (HORIZONTAL_VO2 * metres_per_minute + RESTING_VO2) / RESTING_VO2
+    }
+
+    pub fn kcal_per_minute(&self, speed: Speed) -> f64 {
+        Profile::mets(speed) * self.resting_kcal_per_day() / MINUTES_PER_DAY
+    }
+
+    /// kcal spent walking `distance` in `time`, rests left out. The equation
+    /// is linear in speed, so the totals give the same as adding up every
+    /// second.
+    pub fn calories(&self, distance: Distance, time: Elapsed) -> f64 {
+        let met_minutes = (HORIZONTAL_VO2 * distance.metres() as f64
+            + RESTING_VO2 * time.secs() as f64 / 60.0)
+            / RESTING_VO2;
+        met_minutes * self.resting_kcal_per_day() / MINUTES_PER_DAY
+    }
+}
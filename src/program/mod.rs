@@ -0,0 +1,202 @@
+//! Structured workouts read from TOML files.
+//!
+//! A program is a list of segments, each at one speed until its goal is
+//! reached: some minutes, metres or steps. A segment with several `speeds`
+//! alternates between them every `every_minutes`.
+//!
+//! ```toml
+//! name = "Intervals"
+//!
+//! [[segments]]
+//! name = "Warm-up"
+//! speed = 3.0
+//! minutes = 5
+//!
+//! [[segments]]
+//! name = "Intervals"
+//! speeds = [4.5, 6.0]
+//! every_minutes = 2
+//! minutes = 20
+//!
+//! [[segments]]
+//! name = "Cool-down"
+//! speed = 3.0
+//! metres = 300
+//! ```
+//!
+//! Speeds are in km/h. A speed of 0 stops the belt for a rest, which can only
+//! last some minutes.
+
+pub mod runner;
+pub use runner::{Programs, Progress, Runner, Status};
+
+use crate::controller::units::{Distance, Elapsed, Speed};
+
+use derive_more::{Display, Error as DError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Display, Debug, DError)]
+pub struct ProgramError {
+    pub details: String,
+}
+
+impl From<std::io::Error> for ProgramError {
+    fn from(e: std::io::Error) -> Self {
+        ProgramError {
+            details: e.to_string(),
+        }
+    }
+}
+
+/// When a segment is over, counted from its start.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Goal {
+    Time(Elapsed),
+    Distance(Distance),
+    Steps(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    pub name: String,
+    pub speed: Speed,
+    pub goal: Goal,
+}
+
+/// A program with its alternating segments already split up.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Program {
+    pub name: String,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProgramFile {
+    name: String,
+    segments: Vec<SegmentFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SegmentFile {
+    name: Option<String>,
+    speed: Option<f64>,
+    speeds: Option<Vec<f64>>,
+    every_minutes: Option<f64>,
+    minutes: Option<f64>,
+    metres: Option<u32>,
+    steps: Option<usize>,
+}
+
+impl Program {
+    pub fn read(path: &Path) -> Result<Self, ProgramError> {
+        Program::parse(&std::fs::read_to_string(path)?).map_err(|e| ProgramError {
+            details: format!("{}: {}", path.display(), e.details),
+        })
+    }
+
+    pub fn parse(toml: &str) -> Result<Self, ProgramError> {
+        let file: ProgramFile = toml::from_str(toml).map_err(|e| ProgramError {
+            details: e.to_string(),
+        })?;
+        if file.segments.is_empty() {
+            return Err(error("The program has no segments"));
+        }
+
+        let mut segments = Vec::new();
+        for (index, segment) in file.segments.into_iter().enumerate() {
+            let name = segment
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("Segment {}", index + 1));
+            segment
+                .expand(&name, &mut segments)
+                .map_err(|e| error(&format!("{}: {}", name, e.details)))?;
+        }
+
+        Ok(Program {
+            name: file.name,
+            segments,
+        })
+    }
+}
+
+impl SegmentFile {
+    fn expand(&self, name: &str, segments: &mut Vec<Segment>) -> Result<(), ProgramError> {
+        let goal = match (self.minutes, self.metres, self.steps) {
+            (Some(minutes), None, None) => Goal::Time(Elapsed::from_secs(seconds(minutes)?)),
+            (None, Some(metres), None) if metres > 0 => {
+                Goal::Distance(Distance::from_metres(metres))
+            }
+            (None, None, Some(steps)) if steps > 0 => Goal::Steps(steps),
+            _ => return Err(error("Expected one of minutes, metres or steps above 0")),
+        };
+
+        let speeds = match (self.speed, &self.speeds) {
+            (Some(speed), None) => vec![speed],
+            (None, Some(speeds)) if !speeds.is_empty() => speeds.clone(),
+            _ => return Err(error("Expected either speed or speeds")),
+        };
+        let speeds = speeds
+            .into_iter()
+            .map(|kmh| {
+                if (0.0..=25.5).contains(&kmh) {
+                    Ok(Speed::from_kmh(kmh))
+                } else {
+                    Err(error(&format!("Speed not allowed! {}", kmh)))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if speeds.iter().any(|speed| speed.is_zero()) && !matches!(goal, Goal::Time(_)) {
+            return Err(error("Rests can only last some minutes"));
+        }
+
+        let total = match (goal, self.every_minutes) {
+            (_, None) if speeds.len() == 1 => {
+                segments.push(Segment {
+                    name: name.to_string(),
+                    speed: speeds[0],
+                    goal,
+                });
+                return Ok(());
+            }
+            (Goal::Time(total), Some(_)) => total.secs(),
+            _ => return Err(error("Alternating speeds need minutes and every_minutes")),
+        };
+
+        let every = seconds(self.every_minutes.unwrap_or_default())?;
+        let mut done = 0;
+        for speed in speeds.iter().cycle() {
+            if done >= total {
+                break;
+            }
+            let length = every.min(total - done);
+            segments.push(Segment {
+                name: name.to_string(),
+                speed: *speed,
+                goal: Goal::Time(Elapsed::from_secs(length)),
+            });
+            done += length;
+        }
+        Ok(())
+    }
+}
+
+/// Whole seconds in `minutes`, which must last at least one.
+fn seconds(minutes: f64) -> Result<u32, ProgramError> {
+    let seconds = (minutes * 60.0).round();
+    if seconds >= 1.0 && seconds <= u32::MAX as f64 {
+        Ok(seconds as u32)
+    } else {
+        Err(error(&format!("Duration not allowed! {} minutes", minutes)))
+    }
+}
+
+fn error(details: &str) -> ProgramError {
+    ProgramError {
+        details: details.to_string(),
+    }
+}
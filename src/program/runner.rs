@@ -0,0 +1,345 @@
+//! Drives a pad through a program.
+//!
+//! The runner is a task per program, steered through a `Runner` handle. Time
+//! goals count on the clock while the program runs, distance and step goals
+//! count what the pad reports. Pausing stops the belt and holds the goal of the
+//! segment, resuming starts the belt again at the speed of the segment.
+
+use super::{Goal, Program, ProgramError, Segment};
+use crate::controller::enums::{BeltState, ConnectionEvent, Message, Mode};
+use crate::controller::error::{TransportError, VerifyError};
+use crate::controller::units::Speed;
+use crate::controller::{Pad, State, Transport};
+
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+/// How often the time done is published.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Status {
+    Running,
+    Paused,
+    Finished,
+    Stopped,
+    Failed { reason: String },
+}
+
+impl Status {
+    pub fn is_over(&self) -> bool {
+        !matches!(self, Status::Running | Status::Paused)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub program: String,
+    pub status: Status,
+    /// Index of the current segment.
+    pub segment: usize,
+    pub segments: usize,
+    pub name: String,
+    pub speed: Speed,
+    pub goal: Goal,
+    /// Seconds, metres or steps of the goal done so far.
+    pub done: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Control {
+    Pause,
+    Resume,
+    Skip,
+    Stop,
+}
+
+/// Handle to a running program. Dropping every handle stops it.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    control: mpsc::UnboundedSender<Control>,
+    progress: watch::Receiver<Progress>,
+}
+
+impl Runner {
+    pub fn start<T: Transport>(pad: &Pad<T>, program: Program) -> Runner {
+        let first = &program.segments[0];
+        let progress = Progress {
+            program: program.name.clone(),
+            status: Status::Running,
+            segment: 0,
+            segments: program.segments.len(),
+            name: first.name.clone(),
+            speed: first.speed,
+            goal: first.goal,
+            done: 0,
+        };
+        let (control, commands) = mpsc::unbounded_channel();
+        let (publisher, receiver) = watch::channel(progress.clone());
+
+        let pad = pad.clone();
+        tokio::spawn(async move {
+            let mut task = Task {
+                pad,
+                commands,
+                publisher,
+                progress,
+            };
+            let status = match task.run(&program).await {
+                Ok(status) => status,
+                Err(err) => {
+                    warn!("Program {} failed: {}", program.name, err);
+                    Status::Failed {
+                        reason: err.to_string(),
+                    }
+                }
+            };
+            if let Err(err) = task.pad.stop_belt().await {
+                warn!("Could not stop the belt after the program: {}", err);
+            }
+            info!("Program {} is over: {:?}", program.name, status);
+            task.progress.status = status;
+            task.publish();
+        });
+
+        Runner {
+            control,
+            progress: receiver,
+        }
+    }
+
+    /// Stops the belt and holds the goal of the segment.
+    pub fn pause(&self) {
+        let _ = self.control.send(Control::Pause);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.control.send(Control::Resume);
+    }
+
+    /// Moves on to the next segment, or finishes after the last one.
+    pub fn skip(&self) {
+        let _ = self.control.send(Control::Skip);
+    }
+
+    pub fn stop(&self) {
+        let _ = self.control.send(Control::Stop);
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress.borrow().clone()
+    }
+
+    /// Waits until the program is over.
+    pub async fn finished(&self) -> Progress {
+        let mut progress = self.progress.clone();
+        loop {
+            if progress.borrow().status.is_over() || progress.changed().await.is_err() {
+                return progress.borrow().clone();
+            }
+        }
+    }
+}
+
+struct Task<T: Transport> {
+    pad: Pad<T>,
+    commands: mpsc::UnboundedReceiver<Control>,
+    publisher: watch::Sender<Progress>,
+    progress: Progress,
+}
+
+impl<T: Transport> Task<T> {
+    async fn run(&mut self, program: &Program) -> Result<Status, VerifyError> {
+        info!("Starting program {}", program.name);
+        if self
+            .pad
+            .state()
+            .is_none_or(|state| state.mode != Mode::Manual)
+        {
+            self.pad.switch_mode_verified(Mode::Manual).await?;
+        }
+
+        for (index, segment) in program.segments.iter().enumerate() {
+            info!(
+                "Program {}: {} at {}",
+                program.name, segment.name, segment.speed
+            );
+            self.progress.segment = index;
+            self.progress.name = segment.name.clone();
+            self.progress.speed = segment.speed;
+            self.progress.goal = segment.goal;
+            self.progress.done = 0;
+            self.publish();
+
+            if let Some(status) = self.segment(segment).await? {
+                return Ok(status);
+            }
+        }
+        Ok(Status::Finished)
+    }
+
+    /// Runs the segment until its goal is reached or it is skipped, and
+    /// returns the status if the program ends with it.
+    async fn segment(&mut self, segment: &Segment) -> Result<Option<Status>, VerifyError> {
+        self.drive(segment.speed).await?;
+
+        // The first state is the snapshot the counters are taken from.
+        let mut states = self.pad.register();
+        let mut previous = None;
+        let mut walked = Duration::ZERO;
+        let mut since = Some(Instant::now());
+        let mut tick = tokio::time::interval(PROGRESS_INTERVAL);
+
+        loop {
+            let left = match segment.goal {
+                Goal::Time(time) => {
+                    since.map(|since| time.as_duration().saturating_sub(walked + since.elapsed()))
+                }
+                _ => None,
+            };
+            let deadline = async move {
+                match left {
+                    Some(left) => tokio::time::sleep(left).await,
+                    None => futures::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                biased;
+                command = self.commands.recv() => match command {
+                    Some(Control::Pause) if since.is_some() => {
+                        walked += since.take().map(|since| since.elapsed()).unwrap_or_default();
+                        self.drive(Speed::ZERO).await?;
+                        self.progress.status = Status::Paused;
+                        self.publish();
+                    }
+                    Some(Control::Resume) if since.is_none() => {
+                        self.drive(segment.speed).await?;
+                        since = Some(Instant::now());
+                        self.progress.status = Status::Running;
+                        self.publish();
+                    }
+                    Some(Control::Pause) | Some(Control::Resume) => {}
+                    Some(Control::Skip) => return Ok(None),
+                    Some(Control::Stop) | None => return Ok(Some(Status::Stopped)),
+                },
+                message = states.recv() => match message {
+                    Some(Message::State(state)) => {
+                        if matches!(segment.goal, Goal::Time(_)) {
+                            continue;
+                        }
+                        let current = counter(segment.goal, &state);
+                        // The counters restart from 0 when the pad resets them.
+                        self.progress.done += match previous {
+                            Some(previous) if current >= previous => current - previous,
+                            Some(_) => current,
+                            None => 0,
+                        };
+                        previous = Some(current);
+                        self.publish();
+                        if self.progress.done >= target(segment.goal) {
+                            return Ok(None);
+                        }
+                    }
+                    Some(Message::Connection(ConnectionEvent::Closed)) | None => {
+                        return Err(TransportError::NotConnected.into())
+                    }
+                    Some(Message::Connection(_)) => {}
+                },
+                _ = deadline => return Ok(None),
+                _ = tick.tick() => {
+                    if matches!(segment.goal, Goal::Time(_)) {
+                        let time = walked + since.map(|since| since.elapsed()).unwrap_or_default();
+                        self.progress.done = time.as_secs();
+                        self.publish();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gets the belt to `speed`, starting it first if it stands still.
+    async fn drive(&self, speed: Speed) -> Result<(), VerifyError> {
+        if speed.is_zero() {
+            return self.pad.stop_belt_verified().await;
+        }
+        if self
+            .pad
+            .state()
+            .is_none_or(|state| state.belt_state != BeltState::Moving)
+        {
+            self.pad.start_belt_verified().await?;
+        }
+        Ok(self.pad.change_speed(speed).await?)
+    }
+
+    fn publish(&self) {
+        let _ = self.publisher.send(self.progress.clone());
+    }
+}
+
+/// What the pad counts towards `goal`.
+fn counter(goal: Goal, state: &State) -> u64 {
+    match goal {
+        Goal::Time(_) => state.time.secs() as u64,
+        Goal::Distance(_) => state.distance.metres() as u64,
+        Goal::Steps(_) => state.steps as u64,
+    }
+}
+
+fn target(goal: Goal) -> u64 {
+    match goal {
+        Goal::Time(time) => time.secs() as u64,
+        Goal::Distance(distance) => distance.metres() as u64,
+        Goal::Steps(steps) => steps as u64,
+    }
+}
+
+/// The programs of every pad, by pad id.
+#[derive(Debug, Clone, Default)]
+pub struct Programs {
+    runners: Arc<Mutex<HashMap<String, Runner>>>,
+}
+
+impl Programs {
+    /// Starts `program` on the pad unless another one still runs there.
+    pub fn start<T: Transport>(
+        &self,
+        id: &str,
+        pad: &Pad<T>,
+        program: Program,
+    ) -> Result<Runner, ProgramError> {
+        let mut runners = self.runners.lock().unwrap();
+        if let Some(runner) = runners.get(id) {
+            let progress = runner.progress();
+            if !progress.status.is_over() {
+                return Err(ProgramError {
+                    details: format!("Program {} is still running", progress.program),
+                });
+            }
+        }
+
+        let runner = Runner::start(pad, program);
+        runners.insert(id.to_string(), runner.clone());
+        Ok(runner)
+    }
+
+    /// The program running on the pad, or the last one that ran.
+    pub fn get(&self, id: &str) -> Option<Runner> {
+        self.runners.lock().unwrap().get(id).cloned()
+    }
+
+    /// Stops the program running on the pad and returns it, None if none runs.
+    pub fn stop(&self, id: &str) -> Option<Runner> {
+        let runner = self
+            .get(id)
+            .filter(|runner| !runner.progress().status.is_over())?;
+        runner.stop();
+        Some(runner)
+    }
+}
@@ -0,0 +1,298 @@
+//! Captures of the traffic between `Pad` and the pad, to reproduce bug reports.
+//!
+//! A recording is a JSON lines file of `Event`s: every written command frame
+//! and every notification, with the milliseconds since the recording started.
+//! `Recorder` wraps any transport and writes one, `Replay` is a transport that
+//! plays the notifications of one back at real or accelerated speed.
+
+use crate::controller::error::TransportError;
+use crate::controller::framing::FrameAssembler;
+use crate::controller::protocol::{Command, Response};
+use crate::controller::transport::{Listeners, Notifications, Transport};
+
+use async_trait::async_trait;
+use derive_more::{Display, Error as DError};
+use futures::stream::StreamExt;
+use log::{info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+#[derive(Display, Debug, DError)]
+pub struct RecordingError {
+    pub details: String,
+}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(e: std::io::Error) -> Self {
+        RecordingError {
+            details: e.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Written by us to fe02.
+    Write,
+    /// Notified by the pad on fe01.
+    Notify,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub at_ms: u64,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// Reads a whole recording.
+pub fn read(path: &Path) -> Result<Vec<Event>, RecordingError> {
+    let mut events = vec![];
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push(serde_json::from_str(&line).map_err(|e| RecordingError {
+            details: format!("Line {}: {}", number + 1, e),
+        })?);
+    }
+
+    Ok(events)
+}
+
+/// Writes a whole recording, e.g. one pulled out of a btsnoop capture.
+pub fn write(path: &Path, events: &[Event]) -> Result<(), RecordingError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for event in events {
+        serde_json::to_writer(&mut file, event).map_err(std::io::Error::from)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
+
+/// One line per event with the decoded command or responses. Notifications are
+/// reassembled into frames first, like `Pad` does.
+pub fn timeline(events: &[Event]) -> Vec<String> {
+    let mut assembler = FrameAssembler::new();
+    let mut lines = vec![];
+
+    for event in events {
+        let at = event.at_ms as f64 / 1000.0;
+        match event.direction {
+            Direction::Write => {
+                let decoded = match Command::decode(&event.data) {
+                    Ok(command) => format!("{:?}", command),
+                    Err(e) => format!("! {}", e),
+                };
+                lines.push(format!("{:>10.3}s >> {:?} {}", at, event.data, decoded));
+            }
+            Direction::Notify => {
+                let assembled = assembler.push(&event.data);
+                if assembled.frames.is_empty() && assembled.dropped.is_empty() {
+                    lines.push(format!("{:>10.3}s << {:?} (partial)", at, event.data));
+                }
+                for dropped in assembled.dropped {
+                    lines.push(format!("{:>10.3}s << {:?} ! dropped", at, dropped));
+                }
+                for frame in assembled.frames {
+                    let decoded = match Response::decode(&frame) {
+                        Ok(response) => format!("{:?}", response),
+                        Err(e) => format!("! {}", e),
+                    };
+                    lines.push(format!("{:>10.3}s << {:?} {}", at, frame, decoded));
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+/// Appends events to a recording as they happen.
+#[derive(Debug, Clone)]
+struct Log {
+    file: Arc<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+
+impl Log {
+    fn create(path: &Path) -> Result<Self, RecordingError> {
+        info!("Recording to {}", path.display());
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))),
+            start: Instant::now(),
+        })
+    }
+
+    fn record(&self, direction: Direction, data: &[u8]) {
+        let event = Event {
+            at_ms: self.start.elapsed().as_millis() as u64,
+            direction,
+            data: data.to_vec(),
+        };
+
+        let mut file = self.file.lock().unwrap();
+        let res = serde_json::to_writer(&mut *file, &event)
+            .map_err(std::io::Error::from)
+            .and_then(|_| file.write_all(b"\n"))
+            .and_then(|_| file.flush());
+        if let Err(e) = res {
+            warn!("Recording {:?} failed: {}", event, e);
+        }
+    }
+}
+
+/// Records everything going through `T`.
+pub struct Recorder<T: Transport> {
+    inner: T,
+    log: Log,
+    listeners: Listeners,
+    /// Forwards and records the notifications of the current link.
+    tap: Option<JoinHandle<()>>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Starts a new recording at `path`, overwriting any previous one.
+    pub fn create(inner: T, path: &Path) -> Result<Self, RecordingError> {
+        Ok(Self {
+            inner,
+            log: Log::create(path)?,
+            listeners: Listeners::default(),
+            tap: None,
+        })
+    }
+
+    fn stop_tap(&mut self) {
+        if let Some(tap) = self.tap.take() {
+            tap.abort();
+        }
+        self.listeners.close();
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for Recorder<T> {
+    async fn connect(&mut self) -> Result<(), TransportError> {
+        self.stop_tap();
+        self.inner.connect().await?;
+
+        // A single tap records every notification once, no matter how many
+        // streams are handed out.
+        let mut notifications = self.inner.notifications().await?;
+        let log = self.log.clone();
+        let listeners = self.listeners.clone();
+        self.tap = Some(tokio::spawn(async move {
+            while let Some(data) = notifications.next().await {
+                log.record(Direction::Notify, &data);
+                listeners.send(&data);
+            }
+            listeners.close();
+        }));
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        self.stop_tap();
+        self.inner.disconnect().await
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        self.log.record(Direction::Write, frame);
+        self.inner.write(frame).await
+    }
+
+    async fn notifications(&mut self) -> Result<Notifications, TransportError> {
+        if self.tap.is_none() {
+            return Err(TransportError::NotConnected);
+        }
+        Ok(self.listeners.stream())
+    }
+}
+
+/// Plays the notifications of a recording back, `speed` times faster than they
+/// were recorded. Writes are accepted and only logged. The playback starts on
+/// the first `connect` and carries on across reconnects; once it is over the
+/// pad simply goes quiet.
+pub struct Replay {
+    events: Arc<Vec<Event>>,
+    speed: f64,
+    listeners: Listeners,
+    player: Option<JoinHandle<()>>,
+}
+
+impl Replay {
+    /// Fails unless `speed` is a finite number above 0.
+    pub fn new(events: Vec<Event>, speed: f64) -> Result<Self, RecordingError> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(RecordingError {
+                details: format!("Replay speed must be above 0, got {}", speed),
+            });
+        }
+
+        Ok(Self {
+            events: Arc::new(events),
+            speed,
+            listeners: Listeners::default(),
+            player: None,
+        })
+    }
+
+    pub fn open(path: &Path, speed: f64) -> Result<Self, RecordingError> {
+        Replay::new(read(path)?, speed)
+    }
+}
+
+#[async_trait]
+impl Transport for Replay {
+    async fn connect(&mut self) -> Result<(), TransportError> {
+        if self.player.is_some() {
+            return Ok(());
+        }
+
+        info!("Replaying {} events", self.events.len());
+        let events = Arc::clone(&self.events);
+        let speed = self.speed;
+        let listeners = self.listeners.clone();
+        self.player = Some(tokio::spawn(async move {
+            let start = Instant::now();
+            for event in events.iter() {
+                if event.direction != Direction::Notify {
+                    continue;
+                }
+                let at = Duration::from_millis(event.at_ms).div_f64(speed);
+                tokio::time::sleep_until(start + at).await;
+                listeners.send(&event.data);
+            }
+            info!("Replay finished");
+        }));
+
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        if let Some(player) = self.player.take() {
+            player.abort();
+        }
+        self.listeners.close();
+        Ok(())
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        trace!("Replay ignores write {:?}", frame);
+        Ok(())
+    }
+
+    async fn notifications(&mut self) -> Result<Notifications, TransportError> {
+        Ok(self.listeners.stream())
+    }
+}
@@ -0,0 +1,181 @@
+//! Dead-man switch for the controlling client.
+//!
+//! A client renews the lease of a pad with heartbeats. Once the lease lapses
+//! while the belt moves, `guard` stops the program running on the pad and the
+//! belt, at once and without a cool-down, and records why. A crashed client
+//! does not leave someone walking at the last speed.
+
+use crate::controller::enums::{ConnectionEvent, Message};
+use crate::controller::{Pad, Transport};
+use crate::program::Programs;
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How often `guard` looks at the lease.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When and why the belt was stopped for a lapsed lease.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeaseStop {
+    pub at: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeaseState {
+    /// Seconds a heartbeat lasts.
+    pub timeout: u64,
+    /// Seconds left, 0 once the lease lapsed or before the first heartbeat.
+    pub remaining: f64,
+    pub last_stop: Option<LeaseStop>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    expires: Option<Instant>,
+    last_stop: Option<LeaseStop>,
+    /// Whether the belt was already stopped since it last moved without a lease.
+    enforced: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lease {
+    timeout: Duration,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Lease {
+    /// A lease that still needs its first heartbeat.
+    pub fn new(timeout: Duration) -> Self {
+        Lease {
+            timeout,
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    pub fn renew(&self) -> LeaseState {
+        let mut inner = self.inner.lock().unwrap();
+        inner.expires = Some(Instant::now() + self.timeout);
+        inner.enforced = false;
+        self.state_of(&inner)
+    }
+
+    pub fn state(&self) -> LeaseState {
+        self.state_of(&self.inner.lock().unwrap())
+    }
+
+    fn state_of(&self, inner: &Inner) -> LeaseState {
+        let remaining = inner
+            .expires
+            .map(|expires| expires.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        LeaseState {
+            timeout: self.timeout.as_secs(),
+            remaining: remaining.as_millis() as f64 / 1000.0,
+            last_stop: inner.last_stop.clone(),
+        }
+    }
+
+    /// Why the belt has to stop, if it has to and was not stopped for it yet.
+    fn lapse(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        if inner.enforced {
+            return None;
+        }
+        match inner.expires {
+            None => Some("No heartbeat received".to_string()),
+            Some(expires) if expires <= Instant::now() => Some(format!(
+                "No heartbeat for {} seconds",
+                self.timeout.as_secs()
+            )),
+            Some(_) => None,
+        }
+    }
+
+    /// The belt stands still, so it gets stopped again if it moves without a lease.
+    fn rearm(&self) {
+        self.inner.lock().unwrap().enforced = false;
+    }
+
+    fn enforced(&self, reason: String) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.enforced = true;
+        inner.last_stop = Some(LeaseStop {
+            at: Utc::now(),
+            reason,
+        });
+    }
+}
+
+/// Stops the belt of pad `id` whenever it moves without a valid lease, until
+/// the pad is disconnected. The stop counts once the belt stands still.
+pub fn guard<T: Transport>(
+    id: &str,
+    pad: &Pad<T>,
+    lease: Lease,
+    programs: Programs,
+) -> JoinHandle<()> {
+    let id = id.to_string();
+    let pad = pad.clone();
+    let mut messages = pad.register();
+
+    tokio::spawn(async move {
+        let mut check = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                message = messages.recv() => match message {
+                    Some(Message::Connection(ConnectionEvent::Closed)) | None => break,
+                    Some(_) => continue,
+                },
+                _ = check.tick() => {}
+            }
+
+            let moving = pad.state().is_some_and(|state| !state.speed.is_zero());
+            if !moving {
+                lease.rearm();
+                continue;
+            }
+            if let Some(reason) = lease.lapse() {
+                warn!("{}, stopping the belt", reason);
+                if let Some(runner) = programs.stop(&id) {
+                    // It stops the belt as it ends, which must not come after our stop.
+                    runner.finished().await;
+                    info!("Stopped the program on {}", id);
+                }
+                match pad.stop_belt_verified().await {
+                    Ok(()) => lease.enforced(reason),
+                    Err(e) => warn!("Could not stop the belt: {}", e),
+                }
+            }
+        }
+        info!("Lease guard finished");
+    })
+}
+
+/// The leases of every pad, by pad id. Pads without one are not guarded.
+#[derive(Debug, Clone, Default)]
+pub struct Leases {
+    leases: Arc<Mutex<HashMap<String, Lease>>>,
+}
+
+impl Leases {
+    pub fn insert(&self, id: &str, lease: Lease) {
+        self.leases.lock().unwrap().insert(id.to_string(), lease);
+    }
+
+    pub fn get(&self, id: &str) -> Option<Lease> {
+        self.leases.lock().unwrap().get(id).cloned()
+    }
+
+    /// Renews the lease of the pad if it has one.
+    pub fn renew(&self, id: &str) -> Option<LeaseState> {
+        self.get(id).map(|lease| lease.renew())
+    }
+}
@@ -0,0 +1,20 @@
+//! Stopping the belt when nobody controls or walks on it anymore.
+
+pub mod lease;
+pub use lease::{Lease, LeaseState, Leases};
+
+pub mod walk_off;
+pub use walk_off::{Threshold, WalkOff, WalkOffs, Watchdog};
+
+use serde::Deserialize;
+
+/// The `[safety]` section of the config, everything is off by default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Safety {
+    /// Seconds a heartbeat of the controlling client keeps the belt going.
+    pub lease_secs: Option<u64>,
+    /// Seconds without steps on the moving belt before it stops, by speed.
+    #[serde(default)]
+    pub walk_off: Vec<Threshold>,
+}
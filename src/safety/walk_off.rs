@@ -0,0 +1,183 @@
+//! Noticing that nobody walks on the moving belt anymore.
+//!
+//! When someone steps off, the belt keeps moving but the step counter stalls.
+//! `Watchdog` follows the states and reports a walk-off once the steps stood
+//! still for the threshold of the current speed. Slow walkers take longer
+//! between counted steps, hence a threshold per speed. After a walk-off `watch`
+//! stops the program running on the pad and the belt, and keeps the walk-off
+//! in `WalkOffs` for the API.
+
+use crate::controller::enums::{ConnectionEvent, Message};
+use crate::controller::ramp::Ramp;
+use crate::controller::units::Speed;
+use crate::controller::{Pad, State, Transport};
+use crate::program::Programs;
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long the belt takes to slow down to a stop after a walk-off.
+const SLOW_DOWN: Duration = Duration::from_secs(3);
+
+/// Seconds without steps that make a walk-off at speeds up to `up_to`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    pub up_to: Speed,
+    pub secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WalkOff {
+    pub at: DateTime<Utc>,
+    pub speed: Speed,
+    /// Seconds the steps stood still.
+    pub still_for: u64,
+}
+
+pub struct Watchdog {
+    /// By speed, slowest first.
+    thresholds: Vec<Threshold>,
+    steps: Option<usize>,
+    /// Since when the belt moves without steps.
+    still_since: Option<DateTime<Utc>>,
+    /// Whether a stall was reported since the belt last stood still.
+    reported: bool,
+}
+
+impl Watchdog {
+    pub fn new(mut thresholds: Vec<Threshold>) -> Self {
+        thresholds.sort_by_key(|threshold| threshold.up_to);
+        Watchdog {
+            thresholds,
+            steps: None,
+            still_since: None,
+            reported: false,
+        }
+    }
+
+    /// The threshold of the first speed at least `speed`, the fastest one above
+    /// all of them. None without thresholds.
+    pub fn threshold(&self, speed: Speed) -> Option<Duration> {
+        self.thresholds
+            .iter()
+            .find(|threshold| threshold.up_to >= speed)
+            .or(self.thresholds.last())
+            .map(|threshold| Duration::from_secs(threshold.secs))
+    }
+
+    /// Takes the state the pad reported at `at`, returns the walk-off it shows.
+    pub fn push(&mut self, state: &State, at: DateTime<Utc>) -> Option<WalkOff> {
+        let stepped = self.steps != Some(state.steps);
+        self.steps = Some(state.steps);
+
+        if state.speed.is_zero() {
+            self.still_since = None;
+            self.reported = false;
+            return None;
+        }
+        if stepped {
+            self.still_since = None;
+            return None;
+        }
+
+        let since = *self.still_since.get_or_insert(at);
+        let still_for = (at - since).to_std().unwrap_or_default();
+        match self.threshold(state.speed) {
+            Some(threshold) if still_for >= threshold && !self.reported => {
+                self.reported = true;
+                Some(WalkOff {
+                    at,
+                    speed: state.speed,
+                    still_for: still_for.as_secs(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Watches pad `id` until it is disconnected. Every walk-off stops the program
+/// running on the pad, slows the belt down to a stop, is kept in `walk_offs`
+/// and is sent on the returned channel.
+pub fn watch<T: Transport>(
+    id: &str,
+    pad: &Pad<T>,
+    thresholds: Vec<Threshold>,
+    programs: Programs,
+    walk_offs: WalkOffs,
+) -> mpsc::UnboundedReceiver<WalkOff> {
+    let id = id.to_string();
+    let pad = pad.clone();
+    let mut messages = pad.register();
+    let (events, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut watchdog = Watchdog::new(thresholds);
+        // Stalls are timed on the tokio clock, which tests pause.
+        let (started_at, started) = (Utc::now(), Instant::now());
+        loop {
+            let walk_off = match messages.recv().await {
+                Some(Message::State(state)) => {
+                    let elapsed = chrono::Duration::from_std(started.elapsed()).unwrap_or_default();
+                    watchdog.push(&state, started_at + elapsed)
+                }
+                Some(Message::Connection(ConnectionEvent::Closed)) | None => break,
+                Some(Message::Connection(_)) => continue,
+            };
+
+            if let Some(walk_off) = walk_off {
+                warn!(
+                    "No steps for {} seconds at {}, stopping the belt",
+                    walk_off.still_for, walk_off.speed
+                );
+                walk_offs.insert(&id, walk_off.clone());
+                let _ = events.send(walk_off);
+                tokio::spawn(stop(id.clone(), pad.clone(), programs.clone()));
+            }
+        }
+        info!("Walk-off watchdog finished");
+    });
+
+    rx
+}
+
+async fn stop<T: Transport>(id: String, pad: Pad<T>, programs: Programs) {
+    if let Some(runner) = programs.stop(&id) {
+        // It stops the belt as it ends, which must not come after our stop.
+        runner.finished().await;
+        info!("Stopped the program on {}", id);
+    }
+    if let Err(e) = pad.ramp_to(Speed::ZERO, Ramp::Over(SLOW_DOWN)).await {
+        warn!("Could not slow the belt down: {}", e);
+    }
+    // Any new speed cancels the slow-down, but not this.
+    if let Err(e) = pad.stop_belt_verified().await {
+        warn!("Could not stop the belt: {}", e);
+    }
+}
+
+/// The last walk-off of every pad, by pad id.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOffs {
+    walk_offs: Arc<Mutex<HashMap<String, WalkOff>>>,
+}
+
+impl WalkOffs {
+    pub fn insert(&self, id: &str, walk_off: WalkOff) {
+        self.walk_offs
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), walk_off);
+    }
+
+    pub fn get(&self, id: &str) -> Option<WalkOff> {
+        self.walk_offs.lock().unwrap().get(id).cloned()
+    }
+}
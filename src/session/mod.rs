@@ -0,0 +1,295 @@
+//! Walking sessions told apart in the stream of states.
+//!
+//! The pad only counts time, distance and steps for its current use and resets
+//! the counters between uses. `Tracker` follows the states and turns them into
+//! sessions: one starts when the belt starts moving, pauses when it stops and
+//! resumes when it moves again. It ends once the belt stood still for the pause
+//! timeout, when the pad goes to standby or when its counters are reset.
+
+use crate::controller::enums::{BeltState, ConnectionEvent, Message, Mode};
+use crate::controller::units::{Distance, Elapsed, Speed};
+use crate::controller::{HistoryRecord, Pad, State, Transport};
+use crate::profile::Profile;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long the belt may stand still before the session ends.
+pub const DEFAULT_PAUSE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Where the session stood at some second of it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Seconds since the session started, pauses included.
+    pub offset: u32,
+    pub speed: Speed,
+    pub distance: Distance,
+    pub steps: usize,
+    /// kcal since the session started, with a profile only.
+    pub calories: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Session {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Time spent walking, without the pauses.
+    pub time: Elapsed,
+    pub distance: Distance,
+    pub steps: usize,
+    pub average_speed: Speed,
+    pub max_speed: Speed,
+    /// kcal, with a profile only.
+    pub calories: Option<f64>,
+    /// At most one per second.
+    pub samples: Vec<Sample>,
+}
+
+impl Session {
+    /// A session the pad kept in its history. The pad does not date its
+    /// records, so the session is taken to have ended when it was `fetched_at`,
+    /// which is why it is stored as imported.
+    pub fn from_history(
+        record: &HistoryRecord,
+        fetched_at: DateTime<Utc>,
+        profile: Option<&Profile>,
+    ) -> Session {
+        let (time, distance) = (record.time.secs(), record.distance.metres());
+        let average_speed = if time > 0 {
+            Speed::from_kmh(distance as f64 / time as f64 * 3.6)
+        } else {
+            Speed::ZERO
+        };
+
+        Session {
+            started_at: fetched_at - chrono::Duration::seconds(time.into()),
+            ended_at: fetched_at,
+            time: record.time,
+            distance: record.distance,
+            steps: record.steps,
+            average_speed,
+            // Not in the record, the average is the best guess.
+            max_speed: average_speed,
+            calories: profile.map(|profile| profile.calories(record.distance, record.time)),
+            samples: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    Started { at: DateTime<Utc> },
+    Paused { at: DateTime<Utc> },
+    Resumed { at: DateTime<Utc> },
+    Ended(Session),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    time: u32,
+    distance: u32,
+    steps: usize,
+}
+
+impl From<&State> for Counters {
+    fn from(state: &State) -> Self {
+        Self {
+            time: state.time.secs(),
+            distance: state.distance.metres(),
+            steps: state.steps,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Current {
+    started_at: DateTime<Utc>,
+    last_at: DateTime<Utc>,
+    paused_since: Option<DateTime<Utc>>,
+    /// Counters of the pad right before the session started.
+    baseline: Counters,
+    latest: Counters,
+    max_speed: Speed,
+    samples: Vec<Sample>,
+}
+
+impl Current {
+    /// Counters since the session started.
+    fn walked(&self, counters: Counters) -> Counters {
+        Counters {
+            time: counters.time.saturating_sub(self.baseline.time),
+            distance: counters.distance.saturating_sub(self.baseline.distance),
+            steps: counters.steps.saturating_sub(self.baseline.steps),
+        }
+    }
+
+    fn session(self, profile: Option<&Profile>) -> Session {
+        let Counters {
+            time,
+            distance,
+            steps,
+        } = self.walked(self.latest);
+        let average_speed = if time > 0 {
+            Speed::from_kmh(distance as f64 / time as f64 * 3.6)
+        } else {
+            Speed::ZERO
+        };
+
+        Session {
+            started_at: self.started_at,
+            ended_at: self.paused_since.unwrap_or(self.last_at),
+            time: Elapsed::from_secs(time),
+            distance: Distance::from_metres(distance),
+            steps,
+            average_speed,
+            max_speed: self.max_speed,
+            calories: profile.map(|profile| {
+                profile.calories(Distance::from_metres(distance), Elapsed::from_secs(time))
+            }),
+            samples: self.samples,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Tracker {
+    pause_timeout: Duration,
+    profile: Option<Profile>,
+    previous: Option<State>,
+    current: Option<Current>,
+}
+
+impl Tracker {
+    /// Calories are only estimated with a `profile`.
+    pub fn new(pause_timeout: Duration, profile: Option<Profile>) -> Self {
+        Self {
+            pause_timeout,
+            profile,
+            previous: None,
+            current: None,
+        }
+    }
+
+    /// Follows the state reported `at` and returns what happened to the
+    /// sessions. A counter reset ends one session and may start the next.
+    pub fn push(&mut self, state: &State, at: DateTime<Utc>) -> Vec<SessionEvent> {
+        let mut events = vec![];
+        let counters = Counters::from(state);
+        let reset = self
+            .previous
+            .as_ref()
+            .is_some_and(|previous| counters.time < previous.time.secs());
+
+        let timed_out = self
+            .current
+            .as_ref()
+            .and_then(|current| current.paused_since)
+            .and_then(|since| (at - since).to_std().ok())
+            .is_some_and(|paused| paused >= self.pause_timeout);
+        if reset || timed_out || state.mode == Mode::Standby {
+            events.extend(self.finish().map(SessionEvent::Ended));
+        }
+
+        let moving = state.belt_state == BeltState::Moving;
+        match &mut self.current {
+            None if moving => {
+                let baseline = match &self.previous {
+                    Some(previous) if !reset => Counters::from(previous),
+                    _ => Counters::default(),
+                };
+                self.current = Some(Current {
+                    started_at: at,
+                    last_at: at,
+                    paused_since: None,
+                    baseline,
+                    latest: baseline,
+                    max_speed: Speed::ZERO,
+                    samples: vec![],
+                });
+                events.push(SessionEvent::Started { at });
+            }
+            Some(current) if moving && current.paused_since.is_some() => {
+                current.paused_since = None;
+                events.push(SessionEvent::Resumed { at });
+            }
+            Some(current) if !moving && current.paused_since.is_none() => {
+                current.paused_since = Some(at);
+                events.push(SessionEvent::Paused { at });
+            }
+            _ => {}
+        }
+
+        if let Some(current) = &mut self.current {
+            current.last_at = at;
+            current.latest = counters;
+            current.max_speed = current.max_speed.max(state.speed);
+
+            let offset = (at - current.started_at).num_seconds().max(0) as u32;
+            if current.samples.last().is_none_or(|s| s.offset < offset) {
+                let walked = current.walked(counters);
+                let distance = Distance::from_metres(walked.distance);
+                current.samples.push(Sample {
+                    offset,
+                    speed: state.speed,
+                    distance,
+                    steps: walked.steps,
+                    calories: self
+                        .profile
+                        .as_ref()
+                        .map(|profile| profile.calories(distance, Elapsed::from_secs(walked.time))),
+                });
+            }
+        }
+
+        self.previous = Some(state.clone());
+        events
+    }
+
+    /// Ends the current session, if there is one. It ends when the belt
+    /// stopped, or with the last state if it was still moving.
+    pub fn finish(&mut self) -> Option<Session> {
+        let profile = self.profile.as_ref();
+        self.current.take().map(|current| current.session(profile))
+    }
+}
+
+/// Follows the states of `pad` until it is disconnected and sends what happens
+/// to its sessions. A session also ends when no state arrives for
+/// `pause_timeout`.
+pub fn track<T: Transport>(
+    pad: &Pad<T>,
+    pause_timeout: Duration,
+    profile: Option<Profile>,
+) -> mpsc::UnboundedReceiver<SessionEvent> {
+    let mut messages = pad.register();
+    let (events, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut tracker = Tracker::new(pause_timeout, profile);
+        loop {
+            let happened = match tokio::time::timeout(pause_timeout, messages.recv()).await {
+                Ok(Some(Message::State(state))) => tracker.push(&state, Utc::now()),
+                Ok(Some(Message::Connection(ConnectionEvent::Closed))) | Ok(None) => break,
+                Ok(Some(Message::Connection(_))) => continue,
+                Err(_) => tracker
+                    .finish()
+                    .map(SessionEvent::Ended)
+                    .into_iter()
+                    .collect(),
+            };
+            for event in happened {
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+
+        if let Some(session) = tracker.finish() {
+            let _ = events.send(SessionEvent::Ended(session));
+        }
+    });
+
+    rx
+}
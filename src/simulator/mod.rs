@@ -0,0 +1,131 @@
+//! A WalkingPad simulated in-process.
+//!
+//! `Simulator` is a `Transport`, so a `Pad` drives it exactly like a real pad:
+//! it decodes the written command frames, lets the `Model` catch up with the
+//! time passed since the last command and notifies the answers as whole
+//! frames. Time is taken from tokio, so tests with a paused clock run at full
+//! speed. Clones share the same simulated pad, which lets a test look at the
+//! belt or cut the link while a `Pad` owns the transport.
+
+use crate::controller::error::TransportError;
+use crate::controller::protocol::Command;
+use crate::controller::transport::{Listeners, Notifications, Transport};
+use crate::controller::State;
+
+use async_trait::async_trait;
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+mod model;
+pub use model::Model;
+
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    model: Model,
+    clock: Instant,
+    connected: bool,
+    listeners: Listeners,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Simulator::new(Model::default())
+    }
+}
+
+impl Simulator {
+    pub fn new(model: Model) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                model,
+                clock: Instant::now(),
+                connected: false,
+                listeners: Listeners::default(),
+            })),
+        }
+    }
+
+    /// State of the belt right now, whether anyone asked for it or not.
+    pub fn state(&self) -> State {
+        let mut inner = self.inner.lock().unwrap();
+        inner.advance();
+        inner.model.state()
+    }
+
+    /// Whether someone walks on the belt. Without a walker the belt moves on
+    /// but counts no steps.
+    pub fn set_walking(&self, walking: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.advance();
+        inner.model.set_walking(walking);
+    }
+
+    /// Drops the link as if the pad went out of range. The notifications end
+    /// and writes fail until the next `connect`.
+    pub fn drop_link(&self) {
+        info!("Simulated link dropped");
+        let mut inner = self.inner.lock().unwrap();
+        inner.connected = false;
+        inner.listeners.close();
+    }
+}
+
+impl Inner {
+    fn advance(&mut self) {
+        let now = Instant::now();
+        self.model.advance(now - self.clock);
+        self.clock = now;
+    }
+}
+
+#[async_trait]
+impl Transport for Simulator {
+    async fn connect(&mut self) -> Result<(), TransportError> {
+        info!("Simulated pad connected");
+        self.inner.lock().unwrap().connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), TransportError> {
+        self.drop_link();
+        Ok(())
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.connected {
+            return Err(TransportError::NotConnected);
+        }
+
+        let command = match Command::decode(frame) {
+            Ok(command) => command,
+            Err(e) => {
+                // The pad silently ignores what it does not understand.
+                warn!("Simulated pad ignores {:?}: {}", frame, e);
+                return Ok(());
+            }
+        };
+
+        inner.advance();
+        if let Some(response) = inner.model.handle(command) {
+            inner.listeners.send(&response.encode());
+        }
+
+        Ok(())
+    }
+
+    async fn notifications(&mut self) -> Result<Notifications, TransportError> {
+        let inner = self.inner.lock().unwrap();
+        if !inner.connected {
+            return Err(TransportError::NotConnected);
+        }
+
+        Ok(inner.listeners.stream())
+    }
+}
@@ -0,0 +1,177 @@
+//! What the simulated pad does with the commands it receives.
+//!
+//! Speeds are in tenths of km/h like on the wire. The belt ramps towards the
+//! requested speed at `ACCELERATION` and slows down at `DECELERATION`. Time,
+//! distance and steps only count while the belt moves and wrap around at 24
+//! bits, the width of their fields in the status frame. Steps only count while
+//! someone walks on the belt.
+
+use crate::controller::enums::{BeltState, Mode};
+use crate::controller::protocol::{Command, Preference, Response};
+use crate::controller::units::{Distance, Elapsed, Speed};
+use crate::controller::{HistoryRecord, State};
+
+use std::time::Duration;
+
+/// Tenths of km/h per second.
+const ACCELERATION: f64 = 5.0;
+/// Tenths of km/h per second.
+const DECELERATION: f64 = 10.0;
+/// Metres per step.
+const STEP_LENGTH: f64 = 0.65;
+/// Metres per unit of the distance counter.
+const DISTANCE_UNIT: f64 = 10.0;
+const COUNTER_MASK: u64 = 0xff_ffff;
+/// Longest step integrated at once while the speed changes.
+const MAX_STEP: f64 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct Model {
+    mode: Mode,
+    speed: f64,
+    target: u8,
+    last_speed: u8,
+    max_speed: u8,
+    start_speed: u8,
+    /// Seconds.
+    time: f64,
+    /// Metres.
+    distance: f64,
+    steps: f64,
+    walking: bool,
+}
+
+impl Default for Model {
+    /// A pad fresh after power on: standby, 6 km/h at most, starting at 2 km/h.
+    fn default() -> Self {
+        Self {
+            mode: Mode::Standby,
+            speed: 0.0,
+            target: 0,
+            last_speed: 0,
+            max_speed: 60,
+            start_speed: 20,
+            time: 0.0,
+            distance: 0.0,
+            steps: 0.0,
+            walking: true,
+        }
+    }
+}
+
+impl Model {
+    /// Applies the command and returns what the pad answers, if anything.
+    pub fn handle(&mut self, command: Command) -> Option<Response> {
+        match command {
+            Command::AskStats => {}
+            Command::ChangeSpeed(speed) if speed.is_zero() => self.target = 0,
+            Command::ChangeSpeed(speed) => {
+                if self.mode == Mode::Manual {
+                    self.set_target(speed.tenths_kmh());
+                }
+            }
+            Command::StartBelt => {
+                if self.mode != Mode::Standby && self.target == 0 {
+                    self.set_target(self.start_speed);
+                }
+            }
+            Command::SwitchMode(Mode::Undefined) => {}
+            Command::SwitchMode(mode) => {
+                self.mode = mode;
+                if mode == Mode::Standby {
+                    self.target = 0;
+                }
+            }
+            Command::AskHistory(index) => {
+                return Some(Response::History(HistoryRecord {
+                    index,
+                    time: self.elapsed(),
+                    distance: self.distance(),
+                    steps: Model::counter(self.steps) as usize,
+                }))
+            }
+            Command::SetPreference(Preference::MaxSpeed(speed)) => {
+                self.max_speed = speed.tenths_kmh();
+                self.target = self.target.min(self.max_speed);
+                return None;
+            }
+            Command::SetPreference(Preference::StartSpeed(speed)) => {
+                self.start_speed = speed.tenths_kmh();
+                return None;
+            }
+            Command::SetPreference(_) | Command::AskProfile => return None,
+        }
+
+        Some(Response::Status(self.state()))
+    }
+
+    /// Lets `elapsed` pass on the belt.
+    pub fn advance(&mut self, elapsed: Duration) {
+        let mut left = elapsed.as_secs_f64();
+        while left > 0.0 {
+            let step = if self.speed == self.target as f64 {
+                left
+            } else {
+                left.min(MAX_STEP)
+            };
+            self.step(step);
+            left -= step;
+        }
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            belt_state: if self.speed > 0.0 || self.target > 0 {
+                BeltState::Moving
+            } else {
+                BeltState::Static
+            },
+            speed: Speed::from_tenths_kmh(self.speed.round() as u8),
+            mode: self.mode,
+            time: self.elapsed(),
+            distance: self.distance(),
+            steps: Model::counter(self.steps) as usize,
+            last_speed: Speed::from_tenths_kmh(self.last_speed),
+        }
+    }
+
+    pub fn set_walking(&mut self, walking: bool) {
+        self.walking = walking;
+    }
+
+    fn set_target(&mut self, speed: u8) {
+        self.target = speed.min(self.max_speed);
+        self.last_speed = self.target;
+    }
+
+    fn step(&mut self, seconds: f64) {
+        let target = self.target as f64;
+        if self.speed < target {
+            self.speed = (self.speed + ACCELERATION * seconds).min(target);
+        } else if self.speed > target {
+            self.speed = (self.speed - DECELERATION * seconds).max(target);
+        }
+
+        if self.speed > 0.0 {
+            let metres = self.speed / 36.0 * seconds;
+            self.time += seconds;
+            self.distance += metres;
+            if self.walking {
+                self.steps += metres / STEP_LENGTH;
+            }
+        }
+    }
+
+    fn elapsed(&self) -> Elapsed {
+        Elapsed::from_secs(Model::counter(self.time))
+    }
+
+    /// Counted in whole units, like the pad does.
+    fn distance(&self) -> Distance {
+        Distance::from_metres(Model::counter(self.distance / DISTANCE_UNIT) * DISTANCE_UNIT as u32)
+    }
+
+    fn counter(value: f64) -> u32 {
+        (value as u64 & COUNTER_MASK) as u32
+    }
+}
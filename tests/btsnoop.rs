@@ -0,0 +1,171 @@
+use walkingpad::btsnoop;
+use walkingpad::controller::enums::{BeltState, Mode};
+use walkingpad::controller::protocol::{Command, Response};
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::State;
+use walkingpad::recording::{self, Direction};
+
+const H4: u32 = 1002;
+const MONITOR: u32 = 2001;
+const CONNECTION: u16 = 0x0040;
+const FE01: u16 = 0x000e;
+const FE02: u16 = 0x0010;
+
+struct Record {
+    flags: u32,
+    at_us: u64,
+    packet: Vec<u8>,
+}
+
+fn capture(datalink: u32, records: &[Record]) -> Vec<u8> {
+    let mut data = b"btsnoop\0".to_vec();
+    data.extend(1u32.to_be_bytes());
+    data.extend(datalink.to_be_bytes());
+    for record in records {
+        let len = record.packet.len() as u32;
+        data.extend(len.to_be_bytes());
+        data.extend(len.to_be_bytes());
+        data.extend(record.flags.to_be_bytes());
+        data.extend(0u32.to_be_bytes());
+        data.extend((0x00dc_ddb3_0f2f_8000 + record.at_us).to_be_bytes());
+        data.extend(&record.packet);
+    }
+    data
+}
+
+/// ACL fragments carrying the ATT PDU, split after `split` bytes of L2CAP.
+fn acl(pdu: &[u8], split: usize) -> Vec<Vec<u8>> {
+    let mut l2cap = (pdu.len() as u16).to_le_bytes().to_vec();
+    l2cap.extend(4u16.to_le_bytes());
+    l2cap.extend(pdu);
+
+    let split = split.min(l2cap.len());
+    [&l2cap[..split], &l2cap[split..]]
+        .iter()
+        .filter(|fragment| !fragment.is_empty())
+        .enumerate()
+        .map(|(i, fragment)| {
+            let boundary: u16 = if i == 0 { 0b10 } else { 0b01 };
+            let mut packet = (CONNECTION | boundary << 12).to_le_bytes().to_vec();
+            packet.extend((fragment.len() as u16).to_le_bytes());
+            packet.extend(*fragment);
+            packet
+        })
+        .collect()
+}
+
+fn h4(sent: bool, at_us: u64, pdu: &[u8]) -> Record {
+    Record {
+        flags: !sent as u32,
+        at_us,
+        packet: [&[0x02], &acl(pdu, usize::MAX)[0][..]].concat(),
+    }
+}
+
+fn att(opcode: u8, handle: u16, value: &[u8]) -> Vec<u8> {
+    [&[opcode], &handle.to_le_bytes()[..], value].concat()
+}
+
+fn discovery() -> Vec<u8> {
+    let mut pdu = vec![0x09, 7];
+    for (value_handle, uuid) in [(FE01, 0xfe01u16), (FE02, 0xfe02)] {
+        pdu.extend((value_handle - 1).to_le_bytes());
+        pdu.push(0x10);
+        pdu.extend(value_handle.to_le_bytes());
+        pdu.extend(uuid.to_le_bytes());
+    }
+    pdu
+}
+
+fn status() -> Vec<u8> {
+    Response::Status(State {
+        belt_state: BeltState::Moving,
+        speed: Speed::from_tenths_kmh(30),
+        mode: Mode::Manual,
+        time: Elapsed::from_secs(61),
+        distance: Distance::from_metres(50),
+        steps: 80,
+        last_speed: Speed::from_tenths_kmh(30),
+    })
+    .encode()
+}
+
+#[test]
+fn picks_pad_traffic_by_learnt_handles() {
+    let speed = Command::ChangeSpeed(Speed::from_tenths_kmh(30)).encode();
+    let data = capture(
+        H4,
+        &[
+            h4(true, 0, &[0x08, 0x01, 0x00, 0xff, 0xff, 0x03, 0x28]),
+            h4(true, 1_000, &discovery()),
+            h4(false, 2_000, &discovery()),
+            h4(true, 10_000, &att(0x52, FE02, &speed)),
+            h4(true, 11_000, &att(0x52, 0x0020, &speed)),
+            h4(false, 250_000, &att(0x1b, FE01, &status())),
+        ],
+    );
+
+    let events = btsnoop::parse(&data).unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].direction, Direction::Write);
+    assert_eq!(events[0].data, speed);
+    assert_eq!(events[0].at_ms, 10);
+    assert_eq!(events[1].direction, Direction::Notify);
+    assert_eq!(events[1].data, status());
+    assert_eq!(events[1].at_ms, 250);
+}
+
+#[test]
+fn falls_back_to_headers_and_reassembles() {
+    let mut records = vec![Record {
+        flags: 4,
+        at_us: 0,
+        packet: acl(&att(0x12, 0x0033, &Command::StartBelt.encode()), usize::MAX).remove(0),
+    }];
+    for packet in acl(&att(0x1b, 0x0031, &status()), 9) {
+        records.push(Record {
+            flags: 5,
+            at_us: 500_000,
+            packet,
+        });
+    }
+
+    let events = btsnoop::parse(&capture(MONITOR, &records)).unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].data, Command::StartBelt.encode());
+    assert_eq!(events[1].data, status());
+}
+
+#[test]
+fn annotates_the_timeline() {
+    let data = capture(
+        H4,
+        &[
+            h4(false, 0, &discovery()),
+            h4(
+                true,
+                0,
+                &att(
+                    0x52,
+                    FE02,
+                    &Command::ChangeSpeed(Speed::from_tenths_kmh(30)).encode(),
+                ),
+            ),
+            h4(false, 1_500_000, &att(0x1b, FE01, &status()[..8])),
+            h4(false, 1_520_000, &att(0x1b, FE01, &status()[8..])),
+        ],
+    );
+
+    let lines = recording::timeline(&btsnoop::parse(&data).unwrap());
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("ChangeSpeed(Speed(30))"));
+    assert!(lines[1].contains("(partial)"));
+    assert!(lines[2].trim_start().starts_with("1.520s"));
+    assert!(lines[2].contains("speed: Speed(30)"));
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(btsnoop::parse(b"{\"at_ms\":0}").is_err());
+    assert!(btsnoop::parse(&capture(1, &[])).is_err());
+}
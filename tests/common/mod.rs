@@ -0,0 +1,73 @@
+//! Fixtures shared by the integration tests, each of them uses only some.
+#![allow(dead_code)]
+
+use walkingpad::controller::enums::{BeltState, Mode};
+use walkingpad::controller::registry::Registry;
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::{Pad, State};
+use walkingpad::session::{Sample, Session};
+use walkingpad::simulator::Simulator;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// A simulated pad, already in manual mode.
+pub async fn manual_pad() -> (Pad<Simulator>, Simulator) {
+    let simulator = Simulator::default();
+    let pad = Pad::new(simulator.clone()).await.unwrap();
+    pad.switch_mode_verified(Mode::Manual).await.unwrap();
+    (pad, simulator)
+}
+
+/// The `manual_pad` as `default`.
+pub async fn registry() -> (Registry<Simulator>, Simulator) {
+    let (pad, simulator) = manual_pad().await;
+    let mut registry = Registry::default();
+    registry.insert("default".to_string(), pad);
+    (registry, simulator)
+}
+
+/// A state of the pad in manual mode, moving at any speed but 0.
+pub fn state(tenths_kmh: u8, time: u32, distance: u32, steps: usize) -> State {
+    State {
+        belt_state: if tenths_kmh > 0 {
+            BeltState::Moving
+        } else {
+            BeltState::Static
+        },
+        speed: Speed::from_tenths_kmh(tenths_kmh),
+        mode: Mode::Manual,
+        time: Elapsed::from_secs(time),
+        distance: Distance::from_metres(distance),
+        steps,
+        last_speed: Speed::from_tenths_kmh(tenths_kmh),
+    }
+}
+
+/// `secs` into the tests, from 2023-11-14T22:13:20Z.
+pub fn at(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+}
+
+/// 100 metres in 100 seconds at 3.6 km/h, with a sample every second.
+pub fn session(started_at: i64) -> Session {
+    let started_at = at(started_at);
+    Session {
+        started_at,
+        ended_at: started_at + chrono::Duration::seconds(100),
+        time: Elapsed::from_secs(100),
+        distance: Distance::from_metres(100),
+        steps: 150,
+        average_speed: Speed::from_tenths_kmh(36),
+        max_speed: Speed::from_tenths_kmh(36),
+        calories: Some(6.3),
+        samples: (0..=100)
+            .map(|offset| Sample {
+                offset,
+                speed: Speed::from_tenths_kmh(36),
+                distance: Distance::from_metres(offset / 10 * 10),
+                steps: offset as usize * 3 / 2,
+                calories: None,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+use common::{at, session};
+
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::HistoryRecord;
+use walkingpad::dao::{Dao, SqliteDao};
+use walkingpad::session::Session;
+
+use chrono::{TimeZone, Utc};
+
+#[tokio::test]
+async fn keeps_sessions_with_their_samples() {
+    let dao = SqliteDao::in_memory().unwrap();
+
+    let first = dao.create_session("desk", &session(0)).await.unwrap();
+    let second = dao.create_session("desk", &session(86_400)).await.unwrap();
+
+    let sessions = dao.read_sessions().await.unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].id, second);
+    assert!(sessions[0].session.samples.is_empty());
+
+    let stored = dao.read_session(first).await.unwrap().unwrap();
+    assert_eq!(stored.pad, "desk");
+    assert_eq!(stored.session, session(0));
+
+    assert!(dao.delete_session(first).await.unwrap());
+    assert!(!dao.delete_session(first).await.unwrap());
+    assert!(dao.read_session(first).await.unwrap().is_none());
+    assert_eq!(dao.read_sessions().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn imports_history_records_once() {
+    let dao = SqliteDao::in_memory().unwrap();
+    let record = HistoryRecord {
+        index: 0,
+        time: Elapsed::from_secs(1800),
+        distance: Distance::from_metres(2000),
+        steps: 2500,
+    };
+
+    let fetched_at = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let session = Session::from_history(&record, fetched_at, None);
+    assert_eq!(session.average_speed, Speed::from_tenths_kmh(40));
+    let id = dao.import_session("desk", &session).await.unwrap();
+    assert!(id.is_some());
+
+    // A newer record pushes it to another index an hour later.
+    let later = Session::from_history(
+        &HistoryRecord { index: 1, ..record },
+        fetched_at + chrono::Duration::hours(1),
+        None,
+    );
+    assert_eq!(dao.import_session("desk", &later).await.unwrap(), None);
+    assert!(dao.import_session("hall", &later).await.unwrap().is_some());
+
+    let stored = dao.read_session(id.unwrap()).await.unwrap().unwrap();
+    assert_eq!(stored.session, session);
+    assert!(stored.imported);
+    assert_eq!(dao.read_sessions().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn skips_history_records_tracked_live() {
+    let dao = SqliteDao::in_memory().unwrap();
+    let tracked = session(0);
+    let id = dao.create_session("desk", &tracked).await.unwrap();
+
+    let record = HistoryRecord {
+        index: 0,
+        time: tracked.time,
+        distance: tracked.distance,
+        steps: tracked.steps,
+    };
+    let imported = Session::from_history(&record, at(3600), None);
+    assert_eq!(dao.import_session("desk", &imported).await.unwrap(), None);
+
+    let sessions = dao.read_sessions().await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, id);
+    assert!(!sessions[0].imported);
+}
+
+#[tokio::test]
+async fn updates_settings() {
+    let dao = SqliteDao::in_memory().unwrap();
+
+    assert_eq!(dao.read_setting("units").await.unwrap(), None);
+    dao.update_setting("units", "metric").await.unwrap();
+    dao.update_setting("units", "imperial").await.unwrap();
+    assert_eq!(
+        dao.read_setting("units").await.unwrap().as_deref(),
+        Some("imperial")
+    );
+}
+
+#[tokio::test]
+async fn reopens_a_migrated_database() {
+    let path = std::env::temp_dir().join(format!("walkingpad-dao-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let id = {
+        let dao = SqliteDao::open(&path).unwrap();
+        dao.create_session("default", &session(0)).await.unwrap()
+    };
+    let dao = SqliteDao::open(&path).unwrap();
+    let stored = dao.read_session(id).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(stored.unwrap().session.samples.len(), 101);
+}
@@ -0,0 +1,75 @@
+mod common;
+use common::session;
+
+use walkingpad::export::{self, Format, Route};
+
+fn text(format: Format, route: Option<&Route>) -> String {
+    String::from_utf8(export::export(&session(0), format, route)).unwrap()
+}
+
+/// The CRC of a FIT file, including the CRC at its end, is 0.
+fn fit_crc(data: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xcc01, 0xd801, 0x1400, 0xf001, 0x3c00, 0x2800, 0xe401, 0xa001, 0x6c00, 0x7800,
+        0xb401, 0x5000, 0x9c01, 0x8801, 0x4400,
+    ];
+    data.iter().fold(0, |mut crc, &byte| {
+        for nibble in [byte & 0xf, byte >> 4] {
+            let tmp = TABLE[(crc & 0xf) as usize];
+            crc = ((crc >> 4) & 0x0fff) ^ tmp ^ TABLE[nibble as usize];
+        }
+        crc
+    })
+}
+
+#[test]
+fn writes_a_tcx_lap() {
+    let tcx = text(Format::Tcx, None);
+
+    assert!(tcx.contains("<Id>2023-11-14T22:13:20Z</Id>"));
+    assert!(tcx.contains("<TotalTimeSeconds>100</TotalTimeSeconds>"));
+    assert_eq!(tcx.matches("<Trackpoint>").count(), 101);
+    assert!(tcx.contains("<Time>2023-11-14T22:15:00Z</Time>"));
+    assert!(tcx.contains("<ns3:Speed>1.000</ns3:Speed>"));
+    assert!(tcx.contains("<ns3:Steps>150</ns3:Steps>"));
+    assert!(tcx.contains("<Calories>6</Calories>"));
+}
+
+#[test]
+fn walks_the_gpx_route() {
+    let route = Route::parse_gpx(
+        r#"<gpx><trk><trkseg>
+            <trkpt lat="0.0" lon="0.0"></trkpt>
+            <trkpt
+                lon='0.0005' lat='0'/>
+        </trkseg></trk></gpx>"#,
+    )
+    .unwrap();
+    assert!((route.length() - 55.6).abs() < 0.1);
+
+    let gpx = text(Format::Gpx, Some(&route));
+    assert_eq!(gpx.matches("<trkpt").count(), 101);
+    assert!(gpx.contains(r#"<trkpt lat="0.0000000" lon="0.0000000">"#));
+    // 50 m in, the walk is close to the end of the route.
+    assert!(gpx.contains(r#"<trkpt lat="0.0000000" lon="0.0004497">"#));
+
+    assert!(Route::parse_gpx("<gpx><rtept lat=\"1\" lon=\"2\"/></gpx>").is_err());
+}
+
+#[test]
+fn writes_a_valid_fit_file() {
+    let fit = export::export(&session(0), Format::Fit, None);
+
+    assert_eq!(fit[0], 14);
+    assert_eq!(&fit[8..12], b".FIT");
+    let data_len = u32::from_le_bytes([fit[4], fit[5], fit[6], fit[7]]) as usize;
+    assert_eq!(fit.len(), 14 + data_len + 2);
+    assert_eq!(fit_crc(&fit[..14]), 0);
+    assert_eq!(fit_crc(&fit), 0);
+}
+
+#[test]
+fn parses_formats() {
+    assert_eq!("GPX".parse::<Format>(), Ok(Format::Gpx));
+    assert!("kml".parse::<Format>().is_err());
+}
@@ -0,0 +1,58 @@
+use walkingpad::controller::enums::{BeltState, Mode};
+use walkingpad::controller::framing::FrameAssembler;
+use walkingpad::controller::protocol::Response;
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::State;
+
+fn status(steps: usize) -> Vec<u8> {
+    Response::Status(State {
+        belt_state: BeltState::Moving,
+        speed: Speed::from_tenths_kmh(30),
+        mode: Mode::Manual,
+        time: Elapsed::from_secs(61),
+        distance: Distance::from_metres(50),
+        steps,
+        last_speed: Speed::from_tenths_kmh(30),
+    })
+    .encode()
+}
+
+#[test]
+fn joins_split_frames() {
+    let mut assembler = FrameAssembler::new();
+    let frame = status(80);
+    let (head, tail) = frame.split_at(7);
+
+    let first = assembler.push(head);
+    assert!(first.frames.is_empty());
+    assert!(first.dropped.is_empty());
+
+    let second = assembler.push(tail);
+    assert_eq!(second.frames, [frame]);
+    assert!(second.dropped.is_empty());
+}
+
+#[test]
+fn resyncs_after_garbage() {
+    let mut assembler = FrameAssembler::new();
+    let data = [&[1, 2, 253][..], &status(80), &status(81)].concat();
+
+    let assembled = assembler.push(&data);
+    assert_eq!(assembled.frames, [status(80), status(81)]);
+    assert_eq!(assembled.dropped, [vec![1, 2, 253]]);
+}
+
+#[test]
+fn drops_frames_with_a_bad_crc() {
+    let mut assembler = FrameAssembler::new();
+    let mut bad = status(80);
+    let crc = bad.len() - 2;
+    bad[crc] = bad[crc].wrapping_add(1);
+
+    // The bad frame might still be the start of a longer one.
+    assert!(assembler.push(&bad).frames.is_empty());
+
+    let assembled = assembler.push(&status(81));
+    assert_eq!(assembled.frames, [status(81)]);
+    assert_eq!(assembled.dropped, [bad]);
+}
@@ -0,0 +1,422 @@
+mod common;
+use common::{at, registry, session};
+
+use walkingpad::controller::ramp::Ramps;
+use walkingpad::controller::units::Speed;
+use walkingpad::dao::{Dao, SqliteDao};
+use walkingpad::http::{self, filters::Context};
+use walkingpad::profile::{Profile, Sex};
+use walkingpad::program::Programs;
+use walkingpad::safety::{Lease, Leases, WalkOff, WalkOffs};
+
+use std::sync::Arc;
+use std::time::Duration;
+use warp::hyper::StatusCode;
+
+fn context() -> Context {
+    Context {
+        dao: Arc::new(SqliteDao::in_memory().unwrap()),
+        route: None,
+        profile: None,
+        programs: Programs::default(),
+        leases: Leases::default(),
+        walk_offs: WalkOffs::default(),
+    }
+}
+
+fn body(response: &warp::http::Response<warp::hyper::body::Bytes>) -> String {
+    String::from_utf8(response.body().to_vec()).unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn lists_pads() {
+    let (registry, _) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    let response = warp::test::request().path("/pads").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""id":"default""#));
+    assert!(body(&response).contains(r#""connection":"Connected""#));
+}
+
+#[tokio::test(start_paused = true)]
+async fn drives_the_belt() {
+    let (registry, simulator) = registry().await;
+    let profile = Profile {
+        weight_kg: 70.0,
+        height_cm: 175.0,
+        age: 30,
+        sex: Sex::Male,
+    };
+    let context = Context {
+        profile: Some(profile),
+        ..context()
+    };
+    let api = http::filters::walkingpad(registry, context);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!start_belt")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .path("/pads/default/!change_speed?kmh=3.5")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert_eq!(simulator.state().speed.kmh(), 3.5);
+
+    let response = warp::test::request()
+        .path("/pads/default/state")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""belt_state":"Moving""#));
+    assert!(body(&response).contains(r#""speed":3.5,"#));
+    assert!(body(&response).contains(r#""speed_unit":"km/h""#));
+    assert!(!body(&response).contains(r#""calories":null"#));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!stop_belt")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response), r#""Belt Stopped!""#);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_speed_out_of_range() {
+    let (registry, _) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    for query in [
+        "kmh=6.1",
+        "kmh=-1",
+        "kmh=nan",
+        "mph=inf",
+        "kmh=3&ramp=inf",
+        "speed=61",
+        "speed=2.5",
+        "kmh=3&speed=30",
+    ] {
+        let response = warp::test::request()
+            .path(&format!("/pads/default/!change_speed?{}", query))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn keeps_speed_in_tenths_of_kmh() {
+    let (registry, simulator) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!start_belt")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .path("/pads/default/!change_speed?speed=35")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert_eq!(simulator.state().speed.kmh(), 3.5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn keeps_the_ramps_set() {
+    let (registry, _) = registry().await;
+    let context = context();
+    let dao = context.dao.clone();
+    let api = http::filters::walkingpad(registry.clone(), context);
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/pads/default/ramps")
+        .json(&serde_json::json!({ "warm_up_to": 4.0, "warm_up_secs": 30 }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let ramps = Ramps {
+        warm_up_to: Some(Speed::from_tenths_kmh(40)),
+        warm_up_secs: 30,
+        cool_down_secs: 0,
+    };
+    assert_eq!(registry.get("default").unwrap().ramps(), ramps);
+    assert_eq!(dao.read_ramps("default").await.unwrap(), Some(ramps));
+
+    let response = warp::test::request()
+        .path("/pads/default/ramps")
+        .reply(&api)
+        .await;
+    assert!(body(&response).contains(r#""warm_up_secs":30"#));
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/pads/default/ramps")
+        .json(&serde_json::json!({ "warm_up_to": 9.0 }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(start_paused = true)]
+async fn says_the_belt_is_stopping_during_a_cool_down() {
+    let (registry, simulator) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/pads/default/ramps")
+        .json(&serde_json::json!({ "cool_down_secs": 10 }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for path in ["!start_belt", "!change_speed?kmh=4.0"] {
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/pads/default/{}", path))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", path);
+    }
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!stop_belt")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response), r#""Belt Stopping!""#);
+    assert!(!simulator.state().speed.is_zero());
+
+    tokio::time::sleep(Duration::from_secs(15)).await;
+    assert!(simulator.state().speed.is_zero());
+}
+
+#[tokio::test(start_paused = true)]
+async fn unknown_pad_is_not_found() {
+    let (registry, _) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    let response = warp::test::request()
+        .path("/pads/other/state")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_imperial_units() {
+    let (registry, simulator) = registry().await;
+    let api = http::filters::walkingpad(registry.clone(), context());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!start_belt")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .path("/pads/default/!change_speed?mph=2.5")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+
+    let response = warp::test::request()
+        .path("/pads/default/state?units=imperial")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""speed":2.49,"#));
+    assert!(body(&response).contains(r#""distance_unit":"mi""#));
+    assert!(body(&response).contains(r#""calories":null"#));
+
+    let response = warp::test::request()
+        .path("/pads/default/state?units=furlongs")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(start_paused = true)]
+async fn downloads_sessions() {
+    let (registry, _) = registry().await;
+    let dao: Arc<dyn Dao> = Arc::new(SqliteDao::in_memory().unwrap());
+    let session = session(0);
+    let id = dao.create_session("default", &session).await.unwrap();
+    let api = http::filters::walkingpad(registry, Context { dao, ..context() });
+
+    let response = warp::test::request().path("/sessions").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""pad":"default""#));
+
+    let response = warp::test::request()
+        .path(&format!("/sessions/{}/gpx", id))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/gpx+xml");
+    assert!(body(&response).contains("<trkpt"));
+
+    let response = warp::test::request()
+        .path(&format!("/sessions/{}/fit", id))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(&response.body()[8..12], b".FIT");
+
+    let response = warp::test::request()
+        .path(&format!("/sessions/{}/tcx", id + 1))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(start_paused = true)]
+async fn runs_programs() {
+    let (registry, simulator) = registry().await;
+    let api = http::filters::walkingpad(registry, context());
+
+    let response = warp::test::request()
+        .path("/pads/default/program")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/program")
+        .body("name = \"Easy\"\n[[segments]]\nspeed = 3.5\nminutes = 10\n")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""program":"Easy""#));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/program")
+        .body("name = \"Other\"\n[[segments]]\nspeed = 3.5\nminutes = 10\n")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(simulator.state().speed.kmh(), 3.5);
+
+    let response = warp::test::request()
+        .path("/pads/default/program")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""status":"Running""#));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/program/!stop")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+    let response = warp::test::request()
+        .path("/pads/default/program")
+        .reply(&api)
+        .await;
+    assert!(body(&response).contains(r#""status":"Stopped""#));
+}
+
+#[tokio::test(start_paused = true)]
+async fn renews_leases() {
+    let (registry, _) = registry().await;
+    let context = context();
+    let api = http::filters::walkingpad(registry, context.clone());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!heartbeat")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    context
+        .leases
+        .insert("default", Lease::new(Duration::from_secs(10)));
+    let response = warp::test::request()
+        .path("/pads/default/lease")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""remaining":0.0"#));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/pads/default/!heartbeat")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(&response).contains(r#""timeout":10"#));
+    assert!(body(&response).contains(r#""remaining":10.0"#));
+
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    let response = warp::test::request()
+        .path("/pads/default/!change_speed?kmh=3.0")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = warp::test::request()
+        .path("/pads/default/lease")
+        .reply(&api)
+        .await;
+    let lease: serde_json::Value = serde_json::from_str(&body(&response)).unwrap();
+    assert!(lease["remaining"].as_f64().unwrap() > 9.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_the_last_walk_off() {
+    let (registry, _) = registry().await;
+    let context = context();
+    let api = http::filters::walkingpad(registry, context.clone());
+
+    let response = warp::test::request()
+        .path("/pads/default/walk_off")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response), "null");
+
+    context.walk_offs.insert(
+        "default",
+        WalkOff {
+            at: at(0),
+            speed: Speed::from_tenths_kmh(40),
+            still_for: 4,
+        },
+    );
+    let response = warp::test::request()
+        .path("/pads/default/walk_off")
+        .reply(&api)
+        .await;
+    assert!(body(&response).contains(r#""still_for":4"#));
+}
@@ -0,0 +1,185 @@
+mod common;
+use common::manual_pad;
+
+use walkingpad::controller::enums::{BeltState, ConnectionEvent, Message, Mode};
+use walkingpad::controller::error::VerifyError;
+use walkingpad::controller::protocol::Command;
+use walkingpad::controller::ramp::{Ramp, Ramps};
+use walkingpad::controller::units::Speed;
+use walkingpad::controller::Pad;
+use walkingpad::simulator::{Model, Simulator};
+
+use std::time::Duration;
+
+#[tokio::test(start_paused = true)]
+async fn belt_reaches_requested_speed() {
+    let (pad, simulator) = manual_pad().await;
+
+    pad.start_belt_verified().await.unwrap();
+    pad.change_speed_verified(Speed::from_kmh(4.0))
+        .await
+        .unwrap();
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+
+    tokio::time::sleep(Duration::from_secs(90)).await;
+    let state = simulator.state();
+    assert_eq!(state.belt_state, BeltState::Moving);
+    assert!(state.time.secs() >= 90);
+    assert!(state.distance.metres() >= 90);
+    assert!(state.steps >= 150);
+}
+
+#[tokio::test(start_paused = true)]
+async fn stop_decelerates_to_standstill() {
+    let (pad, simulator) = manual_pad().await;
+
+    pad.start_belt_verified().await.unwrap();
+    pad.change_speed_verified(Speed::from_tenths_kmh(50))
+        .await
+        .unwrap();
+    pad.stop_belt_verified().await.unwrap();
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let state = simulator.state();
+    assert_eq!(state.speed, Speed::ZERO);
+    assert_eq!(state.belt_state, BeltState::Static);
+    assert_eq!(state.last_speed.kmh(), 5.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn standby_ignores_start() {
+    let simulator = Simulator::default();
+    let pad = Pad::new(simulator.clone()).await.unwrap();
+
+    pad.start_belt().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+
+    let state = simulator.state();
+    assert_eq!(state.mode, Mode::Standby);
+    assert_eq!(state.belt_state, BeltState::Static);
+    assert_eq!(pad.state().unwrap().mode, Mode::Standby);
+}
+
+#[tokio::test(start_paused = true)]
+async fn verified_start_times_out_in_standby() {
+    let simulator = Simulator::default();
+    let pad = Pad::new(simulator.clone()).await.unwrap();
+
+    let result = pad.start_belt_verified().await;
+    assert!(matches!(
+        result,
+        Err(VerifyError::Timeout {
+            command: Command::StartBelt,
+            attempts: 3
+        })
+    ));
+    assert_eq!(simulator.state().belt_state, BeltState::Static);
+}
+
+#[tokio::test(start_paused = true)]
+async fn reconnects_after_link_drop() {
+    let (pad, simulator) = manual_pad().await;
+    let mut messages = pad.register();
+
+    simulator.drop_link();
+
+    let mut disconnected = false;
+    while let Some(message) = messages.recv().await {
+        match message {
+            Message::Connection(ConnectionEvent::Disconnected { .. }) => disconnected = true,
+            Message::Connection(ConnectionEvent::Connected) if disconnected => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(pad.connection(), ConnectionEvent::Connected);
+    pad.start_belt_verified().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn fetches_history() {
+    let (pad, _) = manual_pad().await;
+
+    let history = pad.fetch_history().await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].index, 0);
+}
+
+#[test]
+fn counters_wrap_at_24_bits() {
+    let mut model = Model::default();
+    model.handle(Command::SwitchMode(Mode::Manual));
+    model.handle(Command::StartBelt);
+
+    model.advance(Duration::from_secs((1 << 24) + 100));
+    let state = model.state();
+    assert!(state.time.secs() < 1 << 24);
+    assert!(state.time.secs() >= 99);
+    assert!(state.steps < 1 << 24);
+}
+
+#[tokio::test(start_paused = true)]
+async fn ramps_through_intermediate_speeds() {
+    let (pad, simulator) = manual_pad().await;
+    pad.start_belt_verified().await.unwrap();
+    pad.change_speed_verified(Speed::from_kmh(2.0))
+        .await
+        .unwrap();
+
+    let ramp = {
+        let pad = pad.clone();
+        tokio::spawn(async move {
+            pad.ramp_to(Speed::from_kmh(5.0), Ramp::Over(Duration::from_secs(20)))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    let speed = simulator.state().speed.kmh();
+    assert!(speed > 2.5 && speed < 4.5, "{} km/h half way", speed);
+
+    ramp.await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert_eq!(simulator.state().speed.kmh(), 5.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn new_speed_cancels_the_ramp() {
+    let (pad, simulator) = manual_pad().await;
+    pad.start_belt_verified().await.unwrap();
+
+    let ramp = {
+        let pad = pad.clone();
+        tokio::spawn(async move {
+            pad.ramp_to(Speed::from_kmh(6.0), Ramp::Step(Speed::from_kmh(0.2)))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    pad.change_speed(Speed::from_kmh(3.0)).await.unwrap();
+    ramp.await.unwrap().unwrap();
+
+    tokio::time::sleep(Duration::from_secs(20)).await;
+    assert_eq!(simulator.state().speed.kmh(), 3.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn warms_up_and_cools_down() {
+    let (pad, simulator) = manual_pad().await;
+    pad.set_ramps(Ramps {
+        warm_up_to: Some(Speed::from_kmh(4.0)),
+        warm_up_secs: 10,
+        cool_down_secs: 10,
+    });
+
+    pad.start_belt().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+
+    assert!(pad.stop_belt().await.unwrap());
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let speed = simulator.state().speed.kmh();
+    assert!(speed > 0.0 && speed < 4.0, "{} km/h cooling down", speed);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+}
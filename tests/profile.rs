@@ -0,0 +1,47 @@
+use walkingpad::config::Config;
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::profile::{Profile, Sex};
+
+fn profile() -> Profile {
+    Profile {
+        weight_kg: 70.0,
+        height_cm: 175.0,
+        age: 30,
+        sex: Sex::Male,
+    }
+}
+
+#[test]
+fn estimates_walking_calories() {
+    let profile = profile();
+    assert!((profile.resting_kcal_per_day() - 1648.75).abs() < 1e-9);
+    assert!((Profile::mets(Speed::from_kmh(4.2)) - 3.0).abs() < 1e-9);
+
+    // An hour at 4.2 km/h is 3 METs for 60 minutes.
+    let hour = profile.calories(Distance::from_metres(4200), Elapsed::from_secs(3600));
+    assert!((hour - 180.0 * 1648.75 / 1440.0).abs() < 1e-9);
+    assert!((hour - profile.kcal_per_minute(Speed::from_kmh(4.2)) * 60.0).abs() < 1e-9);
+    assert_eq!(
+        profile.calories(Distance::from_metres(0), Elapsed::from_secs(0)),
+        0.0
+    );
+}
+
+#[test]
+fn reads_the_profile_from_config() {
+    let config = Config::parse(
+        r#"
+        [profile]
+        weight_kg = 70
+        height_cm = 175.0
+        age = 30
+        sex = "male"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.profile, Some(profile()));
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    assert!(Config::parse("[profile]\nweight_kg = 70").is_err());
+    assert!(Config::parse("[profil]").is_err());
+}
@@ -0,0 +1,137 @@
+mod common;
+use common::manual_pad;
+
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::program::{Goal, Program, Runner, Status};
+
+use std::time::Duration;
+
+const INTERVALS: &str = r#"
+name = "Intervals"
+
+[[segments]]
+name = "Warm-up"
+speed = 3.0
+minutes = 5
+
+[[segments]]
+name = "Intervals"
+speeds = [4.5, 6.0]
+every_minutes = 2
+minutes = 20
+
+[[segments]]
+speed = 3.0
+metres = 300
+"#;
+
+#[test]
+fn splits_alternating_segments() {
+    let program = Program::parse(INTERVALS).unwrap();
+    assert_eq!(program.name, "Intervals");
+    assert_eq!(program.segments.len(), 12);
+
+    assert_eq!(program.segments[0].name, "Warm-up");
+    assert_eq!(
+        program.segments[0].goal,
+        Goal::Time(Elapsed::from_secs(300))
+    );
+    for (index, segment) in program.segments[1..11].iter().enumerate() {
+        assert_eq!(segment.name, "Intervals");
+        assert_eq!(segment.goal, Goal::Time(Elapsed::from_secs(120)));
+        let speed = if index % 2 == 0 { 45 } else { 60 };
+        assert_eq!(segment.speed, Speed::from_tenths_kmh(speed));
+    }
+    assert_eq!(program.segments[11].name, "Segment 3");
+    assert_eq!(
+        program.segments[11].goal,
+        Goal::Distance(Distance::from_metres(300))
+    );
+}
+
+#[test]
+fn rejects_bad_segments() {
+    let bad = [
+        "speed = 3.0",
+        "speed = 3.0\nminutes = 5\nsteps = 100",
+        "speed = 0.0\nmetres = 100",
+        "speed = 30.0\nminutes = 5",
+        "speeds = [3.0, 4.0]\nmetres = 100",
+        "speed = 3.0\nminutes = 5\nincline = 2",
+    ];
+    for segment in bad {
+        let toml = format!("name = \"Bad\"\n[[segments]]\n{}\n", segment);
+        assert!(Program::parse(&toml).is_err(), "{} was accepted", segment);
+    }
+    assert!(Program::parse("name = \"Empty\"\nsegments = []").is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn runs_time_and_distance_segments() {
+    let (pad, simulator) = manual_pad().await;
+    let program = Program::parse(
+        "name = \"Short\"\n\
+         [[segments]]\nspeed = 5.0\nminutes = 1\n\
+         [[segments]]\nspeed = 4.0\nmetres = 500\n",
+    )
+    .unwrap();
+
+    let runner = Runner::start(&pad, program);
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(simulator.state().speed.kmh(), 5.0);
+    assert_eq!(runner.progress().segment, 0);
+
+    let mut start = simulator.state().distance.metres();
+    while runner.progress().segment == 0 {
+        start = simulator.state().distance.metres();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+    assert_eq!(runner.progress().segment, 1);
+
+    let progress = runner.finished().await;
+    assert_eq!(progress.status, Status::Finished);
+    assert!(progress.done >= 500);
+    // All 500 metres are walked in the second segment, none of the first
+    // minute counts towards it.
+    let walked = simulator.state().distance.metres() - start;
+    assert!((495..520).contains(&walked), "walked {} metres", walked);
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+}
+
+#[tokio::test(start_paused = true)]
+async fn pauses_resumes_and_skips() {
+    let (pad, simulator) = manual_pad().await;
+    let program = Program::parse(
+        "name = \"Two\"\n\
+         [[segments]]\nspeed = 3.0\nminutes = 2\n\
+         [[segments]]\nspeed = 4.0\nminutes = 5\n",
+    )
+    .unwrap();
+
+    let runner = Runner::start(&pad, program);
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    runner.pause();
+    tokio::time::sleep(Duration::from_secs(120)).await;
+    assert!(simulator.state().speed.is_zero());
+    assert_eq!(runner.progress().status, Status::Paused);
+    assert_eq!(runner.progress().segment, 0);
+
+    runner.resume();
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert_eq!(runner.progress().status, Status::Running);
+    assert_eq!(runner.progress().segment, 0);
+    assert_eq!(simulator.state().speed.kmh(), 3.0);
+
+    tokio::time::sleep(Duration::from_secs(40)).await;
+    assert_eq!(runner.progress().segment, 1);
+
+    runner.skip();
+    let progress = runner.finished().await;
+    assert_eq!(progress.status, Status::Finished);
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+}
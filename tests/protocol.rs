@@ -0,0 +1,116 @@
+use walkingpad::controller::enums::{BeltState, Mode, Sensitivity, Target, Units};
+use walkingpad::controller::error::DecodeError;
+use walkingpad::controller::protocol::{Command, Preference, Response};
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::{HistoryRecord, State};
+
+#[test]
+fn encodes_commands_like_the_app() {
+    assert_eq!(Command::AskStats.encode(), [247, 162, 0, 0, 162, 253]);
+    assert_eq!(
+        Command::AskProfile.encode(),
+        [247, 165, 96, 74, 77, 147, 113, 41, 201, 253]
+    );
+}
+
+#[test]
+fn decodes_what_it_encodes_for_commands() {
+    let commands = [
+        Command::AskStats,
+        Command::AskProfile,
+        Command::AskHistory(3),
+        Command::ChangeSpeed(Speed::from_tenths_kmh(45)),
+        Command::ChangeSpeed(Speed::ZERO),
+        Command::SwitchMode(Mode::Manual),
+        Command::StartBelt,
+    ];
+    for command in commands {
+        assert_eq!(Command::decode(&command.encode()), Ok(command));
+    }
+}
+
+#[test]
+fn decodes_what_it_encodes_for_responses() {
+    let responses = [
+        Response::Status(State {
+            belt_state: BeltState::Moving,
+            speed: Speed::from_tenths_kmh(35),
+            mode: Mode::Manual,
+            time: Elapsed::from_secs(4000),
+            distance: Distance::from_metres(3450),
+            steps: 70_000,
+            last_speed: Speed::from_tenths_kmh(30),
+        }),
+        Response::History(HistoryRecord {
+            index: 2,
+            time: Elapsed::from_secs(1800),
+            distance: Distance::from_metres(2000),
+            steps: 2500,
+        }),
+    ];
+    for response in responses {
+        assert_eq!(Response::decode(&response.encode()), Ok(response));
+    }
+}
+
+#[test]
+fn rejects_broken_frames() {
+    assert_eq!(
+        Command::decode(&[247, 162, 0, 0, 163, 253]),
+        Err(DecodeError::BadCrc {
+            expected: 162,
+            actual: 163
+        })
+    );
+    assert_eq!(
+        Command::decode(&[247, 162, 0, 0, 162, 0]),
+        Err(DecodeError::BadTrailer { trailer: 0 })
+    );
+    assert_eq!(
+        Command::decode(&[247, 162, 0]),
+        Err(DecodeError::BadLength {
+            expected: 4,
+            actual: 3
+        })
+    );
+    assert_eq!(
+        Response::decode(&[248, 162, 1, 30, 1, 194, 253]),
+        Err(DecodeError::BadLength {
+            expected: 17,
+            actual: 7
+        })
+    );
+    assert_eq!(
+        Response::decode(&[247, 162, 0, 0, 162, 253]),
+        Err(DecodeError::BadHeader { header: 247 })
+    );
+}
+
+#[test]
+fn encodes_preferences_like_the_app() {
+    let max_speed = Command::SetPreference(Preference::MaxSpeed(Speed::from_tenths_kmh(60)));
+    assert_eq!(max_speed.encode(), [247, 166, 3, 0, 0, 0, 60, 229, 253]);
+
+    let target = Command::SetPreference(Preference::Target(Target::Distance, 1000));
+    assert_eq!(target.encode(), [247, 166, 1, 1, 0, 3, 232, 147, 253]);
+
+    let child_lock = Command::SetPreference(Preference::ChildLock(true));
+    assert_eq!(child_lock.encode(), [247, 166, 5, 0, 0, 0, 1, 172, 253]);
+}
+
+#[test]
+fn decodes_what_it_encodes_for_preferences() {
+    let preferences = [
+        Preference::Target(Target::Time, 1800),
+        Preference::AutoStart(true),
+        Preference::MaxSpeed(Speed::from_tenths_kmh(60)),
+        Preference::StartSpeed(Speed::from_tenths_kmh(20)),
+        Preference::ChildLock(false),
+        Preference::Sensitivity(Sensitivity::Low),
+        Preference::Units(Units::Imperial),
+    ];
+    for preference in preferences {
+        let command = Command::SetPreference(preference);
+        assert_eq!(Command::decode(&command.encode()), Ok(command));
+    }
+}
@@ -0,0 +1,84 @@
+use walkingpad::controller::enums::{BeltState, Message, Mode};
+use walkingpad::controller::protocol::{Command, Response};
+use walkingpad::controller::Pad;
+use walkingpad::recording::{self, Direction, Recorder, Replay};
+use walkingpad::simulator::Simulator;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("walkingpad-{}-{}.jsonl", name, std::process::id()))
+}
+
+async fn record(path: &Path) {
+    let recorder = Recorder::create(Simulator::default(), path).unwrap();
+    let pad = Pad::new(recorder).await.unwrap();
+    pad.switch_mode_verified(Mode::Manual).await.unwrap();
+    pad.start_belt_verified().await.unwrap();
+    pad.disconnect().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn records_writes_and_notifications() {
+    let path = capture_path("record");
+    record(&path).await;
+
+    let events = recording::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let writes: Vec<_> = events
+        .iter()
+        .filter(|event| event.direction == Direction::Write)
+        .map(|event| Command::decode(&event.data).unwrap())
+        .collect();
+    assert!(writes.contains(&Command::SwitchMode(Mode::Manual)));
+    assert!(writes.contains(&Command::StartBelt));
+
+    let moving = events
+        .iter()
+        .filter(|event| event.direction == Direction::Notify)
+        .any(|event| match Response::decode(&event.data).unwrap() {
+            Response::Status(state) => state.belt_state == BeltState::Moving,
+            _ => false,
+        });
+    assert!(moving);
+    assert!(events.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+}
+
+#[tokio::test(start_paused = true)]
+async fn replays_a_recording() {
+    let path = capture_path("replay");
+    record(&path).await;
+
+    let events = recording::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let duration = Duration::from_millis(events.last().unwrap().at_ms);
+
+    let pad = Pad::new(Replay::new(events, 10.0).unwrap()).await.unwrap();
+    let mut messages = pad.register();
+    let replayed = tokio::time::timeout(duration / 5, async {
+        while let Some(message) = messages.recv().await {
+            if let Message::State(state) = message {
+                if state.belt_state == BeltState::Moving {
+                    return true;
+                }
+            }
+        }
+        false
+    })
+    .await;
+
+    assert_eq!(replayed, Ok(true));
+}
+
+#[test]
+fn rejects_replay_speeds_that_never_end() {
+    for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(
+            Replay::new(vec![], speed).is_err(),
+            "{} was accepted",
+            speed
+        );
+    }
+}
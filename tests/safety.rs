@@ -0,0 +1,204 @@
+mod common;
+use common::{at, manual_pad, state};
+
+use walkingpad::controller::ramp::Ramps;
+use walkingpad::controller::units::Speed;
+use walkingpad::program::{Program, Programs, Status};
+use walkingpad::safety::lease::{self, Lease};
+use walkingpad::safety::walk_off::{self, Threshold, WalkOffs, Watchdog};
+
+use std::time::Duration;
+
+const LEASE: Duration = Duration::from_secs(10);
+
+#[tokio::test(start_paused = true)]
+async fn stops_the_belt_once_heartbeats_stop() {
+    let (pad, simulator) = manual_pad().await;
+    let lease = Lease::new(LEASE);
+    lease::guard("default", &pad, lease.clone(), Programs::default());
+
+    lease.renew();
+    pad.start_belt_verified().await.unwrap();
+    pad.change_speed(Speed::from_kmh(3.0)).await.unwrap();
+    for _ in 0..6 {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        lease.renew();
+    }
+    assert_eq!(simulator.state().speed.kmh(), 3.0);
+    assert!(lease.state().remaining > 9.0);
+    assert_eq!(lease.state().last_stop, None);
+
+    tokio::time::sleep(Duration::from_secs(15)).await;
+    assert!(simulator.state().speed.is_zero());
+    let state = lease.state();
+    assert_eq!(state.remaining, 0.0);
+    assert_eq!(
+        state.last_stop.unwrap().reason,
+        "No heartbeat for 10 seconds"
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_a_belt_started_without_heartbeat() {
+    let (pad, simulator) = manual_pad().await;
+    let lease = Lease::new(LEASE);
+    lease::guard("default", &pad, lease.clone(), Programs::default());
+
+    pad.start_belt_verified().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert!(simulator.state().speed.is_zero());
+    assert_eq!(
+        lease.state().last_stop.unwrap().reason,
+        "No heartbeat received"
+    );
+
+    lease.renew();
+    pad.start_belt_verified().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert!(!simulator.state().speed.is_zero());
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_the_program_at_once_when_the_lease_lapses() {
+    let (pad, simulator) = manual_pad().await;
+    pad.set_ramps(Ramps {
+        cool_down_secs: 20,
+        ..Ramps::default()
+    });
+    let programs = Programs::default();
+    let lease = Lease::new(LEASE);
+    lease::guard("default", &pad, lease.clone(), programs.clone());
+
+    let program = Program::parse("name = \"Long\"\n[[segments]]\nspeed = 3.0\nminutes = 10\n");
+    let runner = programs.start("default", &pad, program.unwrap()).unwrap();
+    tokio::time::sleep(Duration::from_secs(8)).await;
+    assert!(simulator.state().speed.is_zero());
+    assert_eq!(runner.progress().status, Status::Stopped);
+    assert!(lease.state().last_stop.is_some());
+
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert!(simulator.state().speed.is_zero());
+}
+
+fn thresholds() -> Vec<Threshold> {
+    vec![
+        Threshold {
+            up_to: Speed::from_kmh(6.0),
+            secs: 4,
+        },
+        Threshold {
+            up_to: Speed::from_kmh(3.0),
+            secs: 8,
+        },
+    ]
+}
+
+#[test]
+fn thresholds_depend_on_speed() {
+    let watchdog = Watchdog::new(thresholds());
+    assert_eq!(
+        watchdog.threshold(Speed::from_kmh(2.0)),
+        Some(Duration::from_secs(8))
+    );
+    assert_eq!(
+        watchdog.threshold(Speed::from_kmh(3.0)),
+        Some(Duration::from_secs(8))
+    );
+    assert_eq!(
+        watchdog.threshold(Speed::from_kmh(4.5)),
+        Some(Duration::from_secs(4))
+    );
+    assert_eq!(
+        watchdog.threshold(Speed::from_kmh(8.0)),
+        Some(Duration::from_secs(4))
+    );
+    assert_eq!(Watchdog::new(vec![]).threshold(Speed::from_kmh(2.0)), None);
+}
+
+#[test]
+fn reports_stalled_steps_once() {
+    let mut watchdog = Watchdog::new(thresholds());
+
+    assert_eq!(watchdog.push(&state(45, 0, 0, 10), at(0)), None);
+    assert_eq!(watchdog.push(&state(45, 0, 0, 12), at(1)), None);
+    assert_eq!(watchdog.push(&state(45, 0, 0, 12), at(2)), None);
+    assert_eq!(watchdog.push(&state(45, 0, 0, 12), at(5)), None);
+    let walk_off = watchdog.push(&state(45, 0, 0, 12), at(6)).unwrap();
+    assert_eq!(walk_off.at, at(6));
+    assert_eq!(walk_off.still_for, 4);
+    assert_eq!(watchdog.push(&state(45, 0, 0, 12), at(7)), None);
+
+    // Steps while the belt slows down do not make another walk-off.
+    assert_eq!(watchdog.push(&state(30, 0, 0, 13), at(8)), None);
+    assert_eq!(watchdog.push(&state(20, 0, 0, 13), at(20)), None);
+
+    // A standing belt counts no steps.
+    assert_eq!(watchdog.push(&state(0, 0, 0, 13), at(21)), None);
+    assert_eq!(watchdog.push(&state(0, 0, 0, 13), at(40)), None);
+
+    // Moving again, slower, where stalls may last longer.
+    assert_eq!(watchdog.push(&state(25, 0, 0, 14), at(41)), None);
+    assert_eq!(watchdog.push(&state(25, 0, 0, 14), at(42)), None);
+    assert_eq!(watchdog.push(&state(25, 0, 0, 14), at(48)), None);
+    assert!(watchdog.push(&state(25, 0, 0, 14), at(50)).is_some());
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_the_belt_after_a_walk_off() {
+    let (pad, simulator) = manual_pad().await;
+    let recorded = WalkOffs::default();
+    let mut walk_offs = walk_off::watch(
+        "default",
+        &pad,
+        thresholds(),
+        Programs::default(),
+        recorded.clone(),
+    );
+
+    pad.start_belt_verified().await.unwrap();
+    pad.change_speed(Speed::from_kmh(4.0)).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+
+    simulator.set_walking(false);
+    let walk_off = walk_offs.recv().await.unwrap();
+    assert_eq!(walk_off.speed.kmh(), 4.0);
+    assert!(walk_off.still_for >= 4);
+
+    assert_eq!(recorded.get("default"), Some(walk_off));
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(simulator.state().speed.is_zero());
+}
+
+#[tokio::test(start_paused = true)]
+async fn stops_the_program_after_a_walk_off() {
+    let (pad, simulator) = manual_pad().await;
+    let programs = Programs::default();
+    let mut walk_offs = walk_off::watch(
+        "default",
+        &pad,
+        thresholds(),
+        programs.clone(),
+        WalkOffs::default(),
+    );
+
+    let program = Program::parse(
+        "name = \"Two\"\n\
+         [[segments]]\nspeed = 4.0\nminutes = 1\n\
+         [[segments]]\nspeed = 5.0\nminutes = 10\n",
+    );
+    let runner = programs.start("default", &pad, program.unwrap()).unwrap();
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(simulator.state().speed.kmh(), 4.0);
+
+    simulator.set_walking(false);
+    walk_offs.recv().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert_eq!(runner.progress().status, Status::Stopped);
+    assert!(simulator.state().speed.is_zero());
+
+    // Past the end of the first segment, nothing restarts the belt.
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert!(simulator.state().speed.is_zero());
+}
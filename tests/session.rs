@@ -0,0 +1,152 @@
+mod common;
+use common::{at, state};
+
+use walkingpad::controller::enums::Mode;
+use walkingpad::controller::units::{Distance, Elapsed, Speed};
+use walkingpad::controller::Pad;
+use walkingpad::profile::{Profile, Sex};
+use walkingpad::session::{self, SessionEvent, Tracker};
+use walkingpad::simulator::Simulator;
+
+use std::time::Duration;
+
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn pauses_resumes_and_ends_after_the_timeout() {
+    let mut tracker = Tracker::new(PAUSE_TIMEOUT, None);
+
+    assert!(tracker.push(&state(0, 0, 0, 0), at(0)).is_empty());
+    assert_eq!(
+        tracker.push(&state(36, 1, 0, 1), at(1)),
+        vec![SessionEvent::Started { at: at(1) }]
+    );
+    tracker.push(&state(36, 100, 100, 150), at(100));
+    assert_eq!(
+        tracker.push(&state(0, 100, 100, 150), at(101)),
+        vec![SessionEvent::Paused { at: at(101) }]
+    );
+    assert_eq!(
+        tracker.push(&state(50, 101, 100, 152), at(130)),
+        vec![SessionEvent::Resumed { at: at(130) }]
+    );
+    tracker.push(&state(50, 200, 240, 350), at(230));
+    tracker.push(&state(0, 200, 240, 350), at(231));
+
+    let events = tracker.push(&state(0, 200, 240, 350), at(291));
+    let session = match &events[..] {
+        [SessionEvent::Ended(session)] => session,
+        _ => panic!("Expected the session to end, got {:?}", events),
+    };
+    assert_eq!(session.started_at, at(1));
+    assert_eq!(session.ended_at, at(231));
+    assert_eq!(session.time, Elapsed::from_secs(200));
+    assert_eq!(session.distance, Distance::from_metres(240));
+    assert_eq!(session.steps, 350);
+    assert_eq!(session.max_speed, Speed::from_tenths_kmh(50));
+    assert_eq!(session.average_speed, Speed::from_kmh(4.3));
+    assert!(tracker.finish().is_none());
+}
+
+#[test]
+fn counter_reset_starts_a_new_session() {
+    let mut tracker = Tracker::new(PAUSE_TIMEOUT, None);
+
+    tracker.push(&state(30, 10, 10, 20), at(0));
+    tracker.push(&state(30, 70, 50, 90), at(60));
+
+    let events = tracker.push(&state(30, 2, 0, 3), at(65));
+    assert_eq!(events.len(), 2);
+    match &events[0] {
+        SessionEvent::Ended(session) => {
+            assert_eq!(session.ended_at, at(60));
+            assert_eq!(session.time, Elapsed::from_secs(70));
+        }
+        event => panic!("Expected the session to end, got {:?}", event),
+    }
+    assert_eq!(events[1], SessionEvent::Started { at: at(65) });
+
+    tracker.push(&state(30, 12, 10, 20), at(75));
+    let session = tracker.finish().unwrap();
+    assert_eq!(session.time, Elapsed::from_secs(12));
+    assert_eq!(session.steps, 20);
+}
+
+#[test]
+fn leaves_out_the_previous_use() {
+    let mut tracker = Tracker::new(PAUSE_TIMEOUT, None);
+
+    tracker.push(&state(0, 300, 400, 600), at(0));
+    tracker.push(&state(40, 301, 400, 602), at(10));
+    tracker.push(&state(40, 302, 410, 604), at(10));
+    tracker.push(&state(40, 303, 410, 606), at(11));
+
+    let session = tracker.finish().unwrap();
+    assert_eq!(session.time, Elapsed::from_secs(3));
+    assert_eq!(session.distance, Distance::from_metres(10));
+    assert_eq!(session.steps, 6);
+    let offsets: Vec<u32> = session.samples.iter().map(|s| s.offset).collect();
+    assert_eq!(offsets, vec![0, 1]);
+    assert_eq!(session.samples[1].steps, 6);
+}
+
+#[test]
+fn standby_ends_the_session() {
+    let mut tracker = Tracker::new(PAUSE_TIMEOUT, None);
+
+    tracker.push(&state(30, 1, 0, 1), at(0));
+    tracker.push(&state(0, 20, 20, 30), at(20));
+
+    let mut standby = state(0, 20, 20, 30);
+    standby.mode = Mode::Standby;
+    let events = tracker.push(&standby, at(25));
+    assert!(matches!(&events[..], [SessionEvent::Ended(session)] if session.ended_at == at(20)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn disconnecting_ends_the_session() {
+    let simulator = Simulator::default();
+    let pad = Pad::new(simulator.clone()).await.unwrap();
+    let mut events = session::track(&pad, PAUSE_TIMEOUT, None);
+
+    pad.switch_mode_verified(Mode::Manual).await.unwrap();
+    pad.start_belt_verified().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    pad.disconnect().await.unwrap();
+
+    assert!(matches!(
+        events.recv().await,
+        Some(SessionEvent::Started { .. })
+    ));
+    match events.recv().await {
+        Some(SessionEvent::Ended(session)) => {
+            assert!(session.time.secs() >= 25);
+            assert!(session.steps > 0);
+            assert_eq!(session.max_speed, Speed::from_tenths_kmh(20));
+        }
+        event => panic!("Expected the session to end, got {:?}", event),
+    }
+    assert!(events.recv().await.is_none());
+}
+
+#[test]
+fn estimates_calories_with_a_profile() {
+    let profile = Profile {
+        weight_kg: 70.0,
+        height_cm: 175.0,
+        age: 30,
+        sex: Sex::Female,
+    };
+    let mut tracker = Tracker::new(PAUSE_TIMEOUT, Some(profile.clone()));
+
+    tracker.push(&state(0, 100, 100, 100), at(0));
+    tracker.push(&state(42, 101, 100, 102), at(1));
+    tracker.push(&state(42, 701, 800, 1000), at(601));
+
+    let session = tracker.finish().unwrap();
+    let expected = profile.calories(Distance::from_metres(700), Elapsed::from_secs(601));
+    assert_eq!(session.calories, Some(expected));
+    let first = profile.calories(Distance::from_metres(0), Elapsed::from_secs(1));
+    assert_eq!(session.samples[0].calories, Some(first));
+    assert_eq!(session.samples[1].calories, Some(expected));
+}